@@ -65,6 +65,13 @@ impl BackendError {
             relevant_lines: line as usize..=line as usize,
         }
     }
+    pub fn invalid_bend_target(line: u32, char: u32) -> Self {
+        BackendError {
+            main_location: ErrorLocation::LineAndChar(line, char),
+            kind: BackendErrorKind::InvalidBendTarget,
+            relevant_lines: line as usize..=line as usize,
+        }
+    }
     pub fn both_slots_multichar(main_line: u32, main_char: u32, other_line: u32) -> Self {
         let min = min(main_line, other_line) as usize;
         let max = max(main_line, other_line) as usize;
@@ -81,6 +88,47 @@ impl BackendError {
             relevant_lines: line as usize..=line as usize,
         }
     }
+    pub fn string_count_mismatch(
+        lines: RangeInclusive<usize>, expected: usize, found: usize,
+    ) -> Self {
+        Self {
+            main_location: ErrorLocation::LineOnly(*lines.start()),
+            relevant_lines: lines,
+            kind: BackendErrorKind::StringCountMismatch { expected, found },
+        }
+    }
+    /// A string of the part in `lines` doesn't have the barline that ends a measure where the
+    /// other strings do.
+    pub fn misaligned_barline(
+        lines: RangeInclusive<usize>, line: usize, measure_in_part: usize, string: usize,
+    ) -> Self {
+        Self {
+            main_location: ErrorLocation::LineAndMeasure(line, measure_in_part),
+            relevant_lines: lines,
+            kind: BackendErrorKind::MisalignedBarline { string },
+        }
+    }
+    pub fn invalid_tuning(line: usize) -> Self {
+        Self {
+            main_location: ErrorLocation::LineOnly(line),
+            relevant_lines: line..=line,
+            kind: BackendErrorKind::InvalidTuning,
+        }
+    }
+    pub fn tuning_mismatch(line: usize, string_count: usize, tuning_len: usize) -> Self {
+        Self {
+            main_location: ErrorLocation::LineOnly(line),
+            relevant_lines: line..=line,
+            kind: BackendErrorKind::TuningMismatch { string_count, tuning_len },
+        }
+    }
+    pub fn invalid_metadata(line: usize, key: &'static str) -> Self {
+        Self {
+            main_location: ErrorLocation::LineOnly(line),
+            relevant_lines: line..=line,
+            kind: BackendErrorKind::InvalidMetadata(key),
+        }
+    }
     pub fn large_fret(line: u32, char: u32) -> Self {
         Self {
             main_location: ErrorLocation::LineAndChar(line, char),
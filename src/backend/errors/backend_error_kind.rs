@@ -8,9 +8,15 @@ pub enum BackendErrorKind {
     FixupFailed,
     InvalidStringName,
     BendOnInvalid,
+    InvalidBendTarget,
     BothSlotsMultiChar,
     MultiBothSlotsFilled,
     FretTooLarge,
+    StringCountMismatch { expected: usize, found: usize },
+    MisalignedBarline { string: usize },
+    InvalidTuning,
+    InvalidMetadata(&'static str),
+    TuningMismatch { string_count: usize, tuning_len: usize },
 }
 
 impl BackendErrorKind {
@@ -48,6 +54,11 @@ impl BackendErrorKind {
                 "You can only bend on frets, and the element before this bend is not a fret."
                     .into(),
             ),
+            BackendErrorKind::InvalidBendTarget => (
+                "Invalid bend target".into(),
+                "A bend goes up to a fret above the bent one, like `7b9`, or by a whole tone with `b1` or a half tone with `b½`."
+                    .into(),
+            ),
             BackendErrorKind::BothSlotsMultiChar => (
                 "Both slots are multichar".into(),
                 "The tick where this element starts is multichar, but has the start of this element as its last logical tick.\nThis is not allowed.".into()
@@ -62,6 +73,31 @@ In this position, you may have:
  - another multichar element of the same cardinality"#.into()
             ),
             BackendErrorKind::FretTooLarge => ("Too large fret".to_string(), "The maximum allowed fret is 99.".to_string()),
+            BackendErrorKind::StringCountMismatch { expected, found } => (
+                "String count mismatch".into(),
+                format!("This part has {found} strings, but the score has {expected} strings per part. If it is for another instrument, label its parts with a line like `Bass:` above them."),
+            ),
+            BackendErrorKind::MisalignedBarline { string } => (
+                "Misaligned barline".into(),
+                format!("The measure doesn't end on the same column on string {} as on the other strings.", string + 1),
+            ),
+            BackendErrorKind::InvalidTuning => (
+                "Invalid tuning".into(),
+                "Failed to parse this tuning. Tunings are note names like `E`, `C#` or `Bb2`, listed from the lowest string.".into(),
+            ),
+            BackendErrorKind::InvalidMetadata(key) => (
+                format!("Invalid {key}"),
+                match *key {
+                    "tempo" => "The tempo should be a number of beats per minute, like `tempo: 120`.".into(),
+                    "capo" => "The capo should be a fret number, like `capo: 2`.".into(),
+                    "time" => "The time signature should look like `time: 3/4`.".into(),
+                    _ => format!("Failed to parse the {key} on this line."),
+                },
+            ),
+            BackendErrorKind::TuningMismatch { string_count, tuning_len } => (
+                "Tuning doesn't match the string count".into(),
+                format!("This tuning lists {tuning_len} strings, but the parts have {string_count} strings."),
+            ),
         }
     }
 }
@@ -6,6 +6,17 @@ pub enum DiagnosticKind {
     CommentInPart,
     FormatAddedBarline,
     FormatReplacedInvalid,
+    /// The strings of the first part have no names, so the standard tuning is used
+    AssumedStandardTuning {
+        string_count: usize,
+    },
+    /// A string line that only the lenient part detector accepts
+    LenientStringLine,
+    /// A measure that is longer or shorter than the time signature of the header
+    MeasureDoesntFitTime {
+        beats: u8,
+        beat_type: u8,
+    },
 }
 
 impl Display for DiagnosticKind {
@@ -23,6 +34,15 @@ impl Display for DiagnosticKind {
             DiagnosticKind::FormatReplacedInvalid => {
                 write!(f, "This character is invalid, so I replaced it with a rest (`-`).")
             }
+            DiagnosticKind::AssumedStandardTuning { string_count } => {
+                write!(f, "The strings aren't named, so I assumed standard tuning for {string_count} strings.")
+            }
+            DiagnosticKind::LenientStringLine => {
+                write!(f, "This string doesn't start like `e|`, so I guessed where its first measure begins.")
+            }
+            DiagnosticKind::MeasureDoesntFitTime { beats, beat_type } => {
+                write!(f, "This measure doesn't add up to {beats}/{beat_type}, so I gave it the time signature it adds up to.")
+            }
         }
     }
 }
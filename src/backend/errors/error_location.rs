@@ -30,6 +30,17 @@ impl ErrorLocation {
             ErrorLocation::LineAndChar(_, c) => Some((*c) as usize),
         }
     }
+    /// The same location on another line
+    pub fn map_line(&self, f: impl FnOnce(usize) -> usize) -> Self {
+        match self {
+            ErrorLocation::NoLocation => ErrorLocation::NoLocation,
+            ErrorLocation::LineOnly(x) => ErrorLocation::LineOnly(f(*x)),
+            ErrorLocation::LineAndMeasure(x, m) => ErrorLocation::LineAndMeasure(f(*x), *m),
+            ErrorLocation::LineAndChar(l, c) => {
+                ErrorLocation::LineAndChar(f(*l as usize) as u32, *c)
+            }
+        }
+    }
     pub fn write_location_explainer(&self, f: &mut impl std::fmt::Write) {
         match self {
             ErrorLocation::NoLocation => (),
@@ -12,7 +12,7 @@ use crate::{
         },
         Backend,
     },
-    parser::{dump_tracks, Parser},
+    parser::{dump_tracks, settings::ParserSettings, Parser},
     time, BufLines,
 };
 
@@ -53,23 +53,27 @@ impl LocationTracker {
 impl Backend for FixupBackend {
     type BackendSettings = FixupBackendSettings;
 
-    fn process<Out: std::io::Write>(
-        parser_input: &BufLines, out: &mut Out, settings: Self::BackendSettings,
+    fn process_with<Out: std::io::Write>(
+        parser_input: &BufLines, parser_settings: ParserSettings, out: &mut Out,
+        settings: Self::BackendSettings,
     ) -> BackendResult {
         if let Some(dump) = settings.dump {
-            let (parse_time, parsed) = time(|| Parser::parse(parser_input));
-            let mut r = BackendResult::new(vec![], None, Some(parse_time), None);
-            let parsed = match parsed {
-                Ok(x) => x,
-                Err((y, x)) => {
-                    r.err = Some(y);
-                    x
+            let (parse_time, parsed) =
+                time(|| Parser::parse_instruments(parser_input, parser_settings));
+            let (instruments, errors) = parsed;
+            let diagnostics = instruments.iter().flat_map(|x| x.diagnostics.iter().cloned());
+            let r = BackendResult::new(diagnostics.collect(), errors, Some(parse_time), None);
+            for parsed in &instruments {
+                if let Some(name) = &parsed.instrument {
+                    writeln!(out, "{name}:").unwrap();
                 }
-            };
-            match dump {
-                FixupDumpOptions::TickStream => writeln!(out, "{:?}", parsed.tick_stream).unwrap(),
-                FixupDumpOptions::PrettyTracks => {
-                    writeln!(out, "{}", dump_tracks(&parsed.as_ref())).unwrap()
+                match dump {
+                    FixupDumpOptions::TickStream => {
+                        writeln!(out, "{:?}", parsed.tick_stream).unwrap()
+                    }
+                    FixupDumpOptions::PrettyTracks => {
+                        writeln!(out, "{}", dump_tracks(&parsed.as_ref())).unwrap()
+                    }
                 }
             }
             return r;
@@ -82,30 +86,36 @@ impl Backend for FixupBackend {
         let mut location_tracker = LocationTracker::new();
         loop {
             let parse_start = Instant::now();
-            let r = Parser::parse(&parser_input);
+            let (_, errors) = Parser::parse_instruments(&parser_input, parser_settings.clone());
             parse_time = parse_start.elapsed();
-            let Err((err, _)) = r else { break };
+            let Some(err) = errors.into_iter().next() else { break };
 
             location_tracker.add(err.main_location.clone());
             if location_tracker.is_same() {
                 let lines = err.main_location.get_line_idx().map(|x| x..=x).unwrap_or(0..=0);
                 return BackendResult::new(
                     diagnostics,
-                    Some(BackendError::fixup_failed(err.main_location.clone(), lines)),
+                    vec![BackendError::fixup_failed(err.main_location.clone(), lines)],
                     Some(parse_time),
                     Some(fixup_start.elapsed()),
                 );
             }
             match err.kind {
                 BackendErrorKind::IOError(_) | BackendErrorKind::FmtError(_) => {
-                    return BackendResult::new(diagnostics, Some(err), Some(parse_time), None);
+                    return BackendResult::new(diagnostics, vec![err], Some(parse_time), None);
                 }
                 BackendErrorKind::BendOnInvalid => {} // TODO: bendOnInvalid fixup: remove the bend
+                BackendErrorKind::InvalidBendTarget => {}
                 BackendErrorKind::InvalidStringName => {}
                 BackendErrorKind::EmptyScore => {}
                 BackendErrorKind::BothSlotsMultiChar => {} // TODO: fix BothSlotsMultichar errors
                 BackendErrorKind::FretTooLarge => {}       // TODO: fix FretTooLarge errors (add
                 // space between)
+                BackendErrorKind::StringCountMismatch { .. }
+                | BackendErrorKind::MisalignedBarline { .. } => {}
+                BackendErrorKind::InvalidTuning
+                | BackendErrorKind::TuningMismatch { .. }
+                | BackendErrorKind::InvalidMetadata(_) => {}
                 BackendErrorKind::MultiBothSlotsFilled => {
                     let Some((line_idx, char_idx)) =
                         err.main_location.get_line_idx().zip(err.main_location.get_char_idx())
@@ -147,7 +157,12 @@ impl Backend for FixupBackend {
         }
         let gen_time = fixup_start.elapsed();
         let joined = parser_input.join("\n");
-        let maybe_io_err = out.write_all(joined.as_bytes()).map_err(BackendError::from).err();
-        BackendResult::new(diagnostics, maybe_io_err, Some(parse_time), Some(gen_time))
+        let io_err = out.write_all(joined.as_bytes()).map_err(BackendError::from).err();
+        BackendResult::new(
+            diagnostics,
+            io_err.into_iter().collect(),
+            Some(parse_time),
+            Some(gen_time),
+        )
     }
 }
@@ -0,0 +1,381 @@
+use midly::{MetaMessage, MidiMessage, Smf, TrackEventKind};
+
+use super::{MidiTracks, ORNAMENT_NOTES, PALM_MUTE_VELOCITY, VELOCITY};
+use crate::{
+    parser::{rhythm::PER_QUARTER, settings::ParserSettings, Parser},
+    BufLines,
+};
+
+/// The duration of a tick in a part without a rhythm line
+const EIGHTH: u32 = PER_QUARTER / 2;
+
+/// The MIDI file of a score, with a track for every instrument
+fn midi_file(score: &str) -> Vec<u8> {
+    let lines = BufLines::from_string(score.into());
+    let (instruments, errors) = Parser::parse_instruments(&lines, ParserSettings::default());
+    assert!(errors.is_empty(), "{errors:?}");
+    let mut tracks = MidiTracks::default();
+    instruments.iter().for_each(|x| tracks.push(x));
+    let mut out = vec![];
+    tracks.write(&mut out).unwrap();
+    out
+}
+
+/// The MIDI file of a score, and the time since its start, the channel and the message of each of
+/// its MIDI events, in the order they are played
+fn midi_events(score: &str) -> Vec<(u32, u8, MidiMessage)> {
+    let out = midi_file(score);
+    let smf = Smf::parse(&out).unwrap();
+    let mut events = vec![];
+    for track in &smf.tracks {
+        let mut time = 0;
+        for event in track {
+            time += event.delta.as_int();
+            if let TrackEventKind::Midi { channel, message } = event.kind {
+                events.push((time, channel.as_int(), message));
+            }
+        }
+    }
+    events.sort_by_key(|x| x.0);
+    events
+}
+
+/// A meta event of the file that isn't about the tracks themselves
+#[derive(Debug, PartialEq)]
+enum Meta {
+    /// Microseconds per quarter note
+    Tempo(u32),
+    /// The numerator, and the denominator as a power of two
+    TimeSignature(u8, u8),
+    Marker(String),
+    Lyric(String),
+}
+
+/// The time since the start of the MIDI file of a score of each of its meta events
+fn meta_events(score: &str) -> Vec<(u32, Meta)> {
+    let out = midi_file(score);
+    let smf = Smf::parse(&out).unwrap();
+    let text = |x: &[u8]| String::from_utf8(x.to_vec()).unwrap();
+    let mut events = vec![];
+    for track in &smf.tracks {
+        let mut time = 0;
+        for event in track {
+            time += event.delta.as_int();
+            let meta = match event.kind {
+                TrackEventKind::Meta(MetaMessage::Tempo(x)) => Meta::Tempo(x.as_int()),
+                TrackEventKind::Meta(MetaMessage::TimeSignature(beats, beat_type, ..)) => {
+                    Meta::TimeSignature(beats, beat_type)
+                }
+                TrackEventKind::Meta(MetaMessage::Marker(x)) => Meta::Marker(text(x)),
+                TrackEventKind::Meta(MetaMessage::Lyric(x)) => Meta::Lyric(text(x)),
+                _ => continue,
+            };
+            events.push((time, meta));
+        }
+    }
+    events.sort_by_key(|x| x.0);
+    events
+}
+
+/// The time and key of every note that is played, in order
+fn note_ons(score: &str) -> Vec<(u32, u8)> {
+    (midi_events(score).into_iter())
+        .filter_map(|x| match x.2 {
+            MidiMessage::NoteOn { key, .. } => Some((x.0, key.as_int())),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn test_bend_against_held_note() {
+    // the high E is held while the G string is bent
+    let score = r#"
+e|12------|
+B|--------|
+G|14b16---|
+D|--------|
+A|--------|
+E|--------|
+"#;
+    let events = midi_events(score);
+    let mut notes: Vec<_> = (events.iter())
+        .filter_map(|x| match x.2 {
+            MidiMessage::NoteOn { key, .. } => Some((key, x.1)),
+            _ => None,
+        })
+        .collect();
+    notes.sort();
+    // the held note is the higher one
+    let [(_, bent), (_, held)] = notes[..] else { panic!("expected two notes: {notes:?}") };
+    assert_ne!(held, bent);
+    let bend_channels: Vec<_> = events
+        .iter()
+        .filter(|x| matches!(x.2, MidiMessage::PitchBend { .. }))
+        .map(|x| x.1)
+        .collect();
+    assert!(!bend_channels.is_empty());
+    assert!(bend_channels.iter().all(|x| *x == bent));
+    // the pitch bend range is set on the channel of every string
+    let range_channels: Vec<_> = (events.iter())
+        .filter(|x| matches!(x.2, MidiMessage::Controller { controller, .. } if controller == 101))
+        .map(|x| x.1)
+        .collect();
+    assert_eq!(range_channels, [0, 1, 2, 3, 4, 5]);
+
+    // without bends, all strings share a channel
+    let events = midi_events(&score.replace("14b16", "14-16"));
+    assert!(events.iter().all(|x| x.1 == 0));
+}
+
+#[test]
+fn test_midi_register() {
+    // the notes are played as written, an octave above how a guitar sounds
+    let score = r#"
+e|0---|
+B|----|
+G|----|
+D|----|
+A|----|
+E|--0-|
+"#;
+    let keys: Vec<_> = note_ons(score).into_iter().map(|x| x.1).collect();
+    assert_eq!(keys, [76, 52]);
+}
+
+#[test]
+fn test_techniques_keep_strings_in_step() {
+    // the `h` takes a tick like a rest, so both strings play their last note together
+    let score = r#"
+e|5h7-5|
+B|0---0|
+G|-----|
+"#;
+    let notes = note_ons(score);
+    assert_eq!(notes, [(0, 69), (0, 59), (2 * EIGHTH, 71), (4 * EIGHTH, 69), (4 * EIGHTH, 59)]);
+    // the same goes for the ticks of ornaments, where `tr` is a single tick
+    let notes = note_ons(&score.replace("5h7-5", "5tr-5"));
+    assert_eq!(notes[notes.len() - 2..], [(3 * EIGHTH, 69), (3 * EIGHTH, 59)]);
+}
+
+#[test]
+fn test_rhythm_durations() {
+    // every tick takes as long as the note value above it
+    let score = r#"
+  Q   E.S E3E3E3|H
+e|0---2-3-5-5-5-|7---|
+B|--------------|----|
+G|--------------|----|
+D|--------------|----|
+A|--------------|----|
+E|--------------|----|
+"#;
+    let times: Vec<_> = note_ons(score).into_iter().map(|x| x.0).collect();
+    assert_eq!(times, [0, 96, 168, 192, 224, 256, 288]);
+    let last_off = (midi_events(score).into_iter())
+        .filter(|x| matches!(x.2, MidiMessage::NoteOff { .. }))
+        .map(|x| x.0)
+        .max();
+    assert_eq!(last_off, Some(288 + 2 * PER_QUARTER));
+}
+
+#[test]
+fn test_tempo_and_time() {
+    let score = r#"tempo: 90
+time: 3/4
+e|0-----|
+B|------|
+G|------|
+D|------|
+A|------|
+E|------|
+"#;
+    assert_eq!(meta_events(score), [(0, Meta::TimeSignature(3, 2)), (0, Meta::Tempo(666_666))]);
+    // without a header, the file is in 4/4 at 80 bpm
+    let score = score.lines().skip(2).collect::<Vec<_>>().join("\n");
+    assert_eq!(meta_events(&score), [(0, Meta::TimeSignature(4, 2)), (0, Meta::Tempo(750_000))]);
+}
+
+#[test]
+fn test_repeats_are_unrolled() {
+    let score = r#"
+e|:-0-|-1-:|-2-|
+B|:---|---:|---|
+G|:---|---:|---|
+D|:---|---:|---|
+A|:---|---:|---|
+E|:---|---:|---|
+
+e|-3-|-4-|  x3
+B|---|---|
+G|---|---|
+D|---|---|
+A|---|---|
+E|---|---|
+"#;
+    let notes = note_ons(score);
+    let keys: Vec<_> = notes.iter().map(|x| x.1 - 76).collect();
+    assert_eq!(keys, [0, 1, 0, 1, 2, 3, 4, 3, 4, 3, 4]);
+    // every measure is three ticks long, and each note is in the middle of one
+    let times: Vec<_> = notes.iter().map(|x| x.0).collect();
+    let expected: Vec<_> = (0..11).map(|x| (3 * x + 1) * EIGHTH).collect();
+    assert_eq!(times, expected);
+}
+
+#[test]
+fn test_palm_mute() {
+    // palm muted notes are softer and only last half of their tick
+    let score = r#"
+  PM--|
+e|0---|0---|
+B|----|----|
+G|----|----|
+D|----|----|
+A|----|----|
+E|----|----|
+"#;
+    let notes: Vec<_> = (midi_events(score).into_iter())
+        .filter_map(|x| match x.2 {
+            MidiMessage::NoteOn { vel, .. } => Some((x.0, "on", vel.as_int())),
+            MidiMessage::NoteOff { .. } => Some((x.0, "off", 0)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        notes,
+        [
+            (0, "on", PALM_MUTE_VELOCITY),
+            (EIGHTH / 2, "off", 0),
+            (4 * EIGHTH, "on", VELOCITY),
+            (5 * EIGHTH, "off", 0)
+        ]
+    );
+}
+
+#[test]
+fn test_ornaments() {
+    // a trill alternates with the note a whole step above, and a tremolo repeats the note, in
+    // notes that fill the tick of the note
+    let score = r#"
+e|-------|
+B|-------|
+G|---7tr-|
+D|-----9=|
+A|-------|
+E|-------|
+"#;
+    let notes = note_ons(score);
+    let (trill, tremolo): (Vec<_>, Vec<_>) = notes.into_iter().partition(|x| x.1 != 71);
+    let step = EIGHTH / ORNAMENT_NOTES;
+    let trill_start = 3 * EIGHTH;
+    assert_eq!(
+        trill,
+        [
+            (trill_start, 74),
+            (trill_start + step, 76),
+            (trill_start + 2 * step, 74),
+            (trill_start + 3 * step, 76)
+        ]
+    );
+    let tremolo_start = 4 * EIGHTH;
+    let expected: Vec<_> = (0..ORNAMENT_NOTES).map(|x| (tremolo_start + x * step, 71)).collect();
+    assert_eq!(tremolo, expected);
+}
+
+#[test]
+fn test_markers_and_lyrics() {
+    // sections and chords are markers, and syllables that continue end with a hyphen
+    let score = r#"[Intro]
+  Am       G
+e|0---0---|3---|
+B|1---1---|0---|
+G|2---2---|0---|
+D|2---2---|0---|
+A|0---0---|2---|
+E|--------|----|
+  Hel-lo   world
+"#;
+    let texts: Vec<_> = meta_events(score).into_iter().skip(2).collect();
+    assert_eq!(
+        texts,
+        [
+            (0, Meta::Marker("Intro".into())),
+            (0, Meta::Marker("Am".into())),
+            (0, Meta::Lyric("Hel-".into())),
+            (4 * EIGHTH, Meta::Lyric("lo".into())),
+            (8 * EIGHTH, Meta::Marker("G".into())),
+            (8 * EIGHTH, Meta::Lyric("world".into())),
+        ]
+    );
+}
+
+#[test]
+fn test_instrument_channels() {
+    let score = r#"
+Gtr I
+e|0---|
+B|----|
+G|----|
+D|----|
+A|----|
+E|----|
+Bass:
+G|----|
+D|----|
+A|3---|
+E|----|
+
+Gtr I
+e|3---|
+B|----|
+G|----|
+D|----|
+A|----|
+E|----|
+"#;
+    // every instrument has a channel of its own, and the parts of an instrument follow each other
+    let channels = |score: &str| {
+        (midi_events(score).into_iter())
+            .filter_map(|x| match x.2 {
+                MidiMessage::NoteOn { key, .. } => Some((x.0, x.1, key.as_int())),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(channels(score), [(0, 0, 76), (0, 1, 60), (4 * EIGHTH, 0, 79)]);
+    // with bends, every string of the guitar has a channel, and the bass takes the next one. `b7`
+    // is a single tick, so the first part is a tick shorter
+    let bent = score.replacen("e|0---|", "e|5b7-|", 1);
+    assert_eq!(channels(&bent), [(0, 0, 81), (0, 6, 60), (3 * EIGHTH, 0, 79)]);
+}
+
+#[test]
+fn test_palm_muted_bend() {
+    // a palm muted bend is softer and shorter like any other palm muted note, but the string stays
+    // in step with the others
+    let score = r#"
+  PM---|
+e|5b7-5|
+B|0---0|
+G|-----|
+"#;
+    let events: Vec<_> = (midi_events(score).into_iter())
+        .filter_map(|x| match x.2 {
+            MidiMessage::NoteOn { key, vel } => Some((x.0, key.as_int(), vel.as_int())),
+            MidiMessage::NoteOff { key, .. } => Some((x.0, key.as_int(), 0)),
+            _ => None,
+        })
+        .collect();
+    let e: Vec<_> = events.iter().filter(|x| x.1 == 69).collect();
+    assert_eq!(
+        e,
+        [
+            &(0, 69, PALM_MUTE_VELOCITY),
+            &(EIGHTH / 2 + EIGHTH, 69, 0),
+            &(3 * EIGHTH, 69, PALM_MUTE_VELOCITY),
+            &(3 * EIGHTH + EIGHTH / 2, 69, 0)
+        ]
+    );
+    let b: Vec<_> = events.iter().filter(|x| x.1 == 59 && x.2 > 0).map(|x| x.0).collect();
+    assert_eq!(b, [0, 3 * EIGHTH]);
+}
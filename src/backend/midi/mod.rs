@@ -1,13 +1,22 @@
-use std::{collections::HashMap, iter, time::Instant};
+use std::{
+    io::BufRead,
+    time::{Duration, Instant},
+};
 
 use midly::{
     num::{u28, u7},
-    Format, Header, MetaMessage, MidiMessage, Smf, TrackEvent, TrackEventKind,
+    Format, Header, MetaMessage, MidiMessage, PitchBend, Smf, TrackEvent, TrackEventKind,
 };
 use tracing::trace;
 
-use super::{Backend, BackendResult};
-use crate::{parser::ParserResult, time};
+use super::{errors::backend_error::BackendError, Backend, BackendResult};
+use crate::{
+    parser::{
+        annotation::AnnotationKind, lyrics::Syllabic, playback_order, rhythm::PER_QUARTER,
+        settings::ParserSettings, stream::PartStream, ParserResult,
+    },
+    time,
+};
 use crate::{
     parser::{
         tab_element::TabElement::{self, Fret},
@@ -16,114 +25,508 @@ use crate::{
     BufLines,
 };
 
+#[cfg(test)]
+mod midi_tests;
+
+/// Used when the score doesn't specify a tempo
 const BPM: u32 = 80;
 const MINUTE_IN_MS: u32 = 60 * 1000;
 const MINUTE_IN_US: u32 = MINUTE_IN_MS * 1000;
-const LENGTH_OF_QUARTER: u32 = MINUTE_IN_US / BPM;
-const LENGTH_OF_EIGHTH: u32 = 1;
+/// MIDI ticks per quarter note, the same unit as the durations of the parser
+const RESOLUTION: u16 = PER_QUARTER as u16;
+const VELOCITY: u8 = 100;
+/// Guitar music is written an octave higher than it sounds, and the notes are played as they are
+/// written, like the muxml backend writes them
+const WRITTEN_OCTAVE: u8 = 12;
+/// Palm muted notes are cut short and played softer
+const PALM_MUTE_VELOCITY: u8 = 70;
+const GHOST_NOTE_VELOCITY: u8 = 50;
+const ACCENT_VELOCITY: u8 = 120;
+/// Trilled and tremolo picked notes are split into this many notes
+const ORNAMENT_NOTES: u32 = 4;
+/// A trill alternates with the note a whole step above
+const TRILL_INTERVAL: u8 = 2;
+/// The pitch bend range that is set for scores with bends, in semitones each way
+const BEND_RANGE: u8 = 12;
+/// A bend or release is played as this many pitch bend events, spread over its tick
+const BEND_STEPS: u32 = 8;
+/// The channels that instruments can be played on, which are all but the one for percussion
+const MAX_CHANNELS: usize = 15;
 
 pub struct MidiBackend();
 impl Backend for MidiBackend {
     type BackendSettings = ();
 
-    fn process<Out: std::io::Write>(
-        input: &BufLines, out: &mut Out, _settings: Self::BackendSettings,
+    fn process_with<Out: std::io::Write>(
+        input: &BufLines, parser_settings: ParserSettings, out: &mut Out,
+        _settings: Self::BackendSettings,
     ) -> BackendResult {
-        let diagnostics = vec![];
-        let (parse_time, parsed0) = time(|| Parser::parse(input));
-        let parsed = match parsed0 {
-            Ok(x) => x,
-            Err(y) => return BackendResult::new(diagnostics, Some(y.0), Some(parse_time), None),
-        };
+        let recover = parser_settings.recover;
+        let (parse_time, (instruments, mut errors)) =
+            time(|| Parser::parse_instruments(input, parser_settings));
+        let diagnostics = instruments.iter().flat_map(|x| x.diagnostics.iter().cloned()).collect();
+        if !errors.is_empty() && !recover {
+            return BackendResult::new(diagnostics, errors, Some(parse_time), None);
+        }
         // TODO: the parser now gives us things like tick count, can probably preallocate based on
         // that
         let gen_start = Instant::now();
-        let mut midi_tracks = convert_to_midi(&parsed);
-        //diagnostics.extend(parse_result.diagnostics);
-        trace!(LENGTH_OF_QUARTER, "Length of quarter");
-        let mut tracks = vec![vec![
+        let mut tracks = MidiTracks::default();
+        instruments.iter().for_each(|x| tracks.push(x));
+        let gen_time = gen_start.elapsed();
+        if let Err(x) = tracks.write(out) {
+            errors.push(x);
+        };
+        BackendResult::new(diagnostics, errors, Some(parse_time), Some(gen_time))
+    }
+}
+impl MidiBackend {
+    /// Like [Backend::process_with], but parses the input while reading it and converts it part by
+    /// part, so the input is never in memory as a whole.
+    pub fn process_stream<In: BufRead, Out: std::io::Write>(
+        input: In, parser_settings: ParserSettings, out: &mut Out,
+    ) -> BackendResult {
+        let recover = parser_settings.recover;
+        let (mut errors, mut diagnostics) = (vec![], vec![]);
+        let mut tracks = MidiTracks::default();
+        let (mut parse_time, mut gen_time) = (Duration::ZERO, Duration::ZERO);
+        let mut parts = PartStream::new(input, parser_settings);
+        loop {
+            let (part_time, part) = time(|| parts.next());
+            parse_time += part_time;
+            match part {
+                None => break,
+                Some(Ok(part)) => {
+                    gen_time += time(|| tracks.push(&part)).0;
+                    diagnostics.extend(part.diagnostics);
+                }
+                Some(Err(err)) => errors.push(err),
+            }
+        }
+        if !errors.is_empty() && !recover {
+            return BackendResult::new(diagnostics, errors, Some(parse_time), None);
+        }
+        let (write_time, written) = time(|| tracks.write(out));
+        if let Err(x) = written {
+            errors.push(x);
+        }
+        BackendResult::new(diagnostics, errors, Some(parse_time), Some(gen_time + write_time))
+    }
+}
+
+/// A meta event with text. The track events only borrow their text, so these are turned into
+/// track events when the file is written.
+#[derive(Clone)]
+enum TextEvent {
+    Marker(String),
+    Lyric(String),
+}
+
+/// The tracks of a MIDI file, made from parsed scores. Every instrument of the score has a track
+/// and a channel of its own, or one for each string if it has bends. Scores of the same instrument that are pushed one after another are
+/// played one after another, so a score can be converted part by part.
+#[derive(Default)]
+pub struct MidiTracks {
+    /// In the order they were first pushed
+    instruments: Vec<InstrumentTracks>,
+    tempo: Option<u32>,
+    time: Option<(u8, u8)>,
+}
+/// The events of an instrument. Every string has a track of its own while the score is read, and
+/// they are merged into one when the file is written.
+#[derive(Default)]
+struct InstrumentTracks {
+    name: Option<String>,
+    /// One track for every string
+    tracks: Vec<Vec<TrackEvent<'static>>>,
+    /// The time since the last event of each string
+    delta_carry_on: Vec<u28>,
+    /// The time since the previous text event, and the event
+    texts: Vec<(u32, TextEvent)>,
+    /// The time since the last text event
+    text_delta: u32,
+    /// Whether any note is bent, so that the pitch bend range has to be set
+    bends: bool,
+}
+impl MidiTracks {
+    pub fn push(&mut self, parsed: &ParserResult) {
+        self.tempo = parsed.metadata.tempo.or(self.tempo);
+        self.time = parsed.metadata.time.or(self.time);
+        let idx = self.instruments.iter().position(|x| x.name == parsed.instrument);
+        let idx = idx.unwrap_or_else(|| {
+            let name = parsed.instrument.clone();
+            self.instruments.push(InstrumentTracks { name, ..Default::default() });
+            self.instruments.len() - 1
+        });
+        self.instruments[idx].push_notes(parsed);
+        self.instruments[idx].push_texts(parsed);
+    }
+    pub fn write<Out: std::io::Write>(self, out: &mut Out) -> Result<(), BackendError> {
+        let MidiTracks { mut instruments, tempo, time: time_signature } = self;
+        let string_tracks: Vec<_> =
+            instruments.iter_mut().map(|x| std::mem::take(&mut x.tracks)).collect();
+        let length_of_quarter = MINUTE_IN_US / tempo.unwrap_or(BPM);
+        trace!(length_of_quarter, "Length of quarter");
+        let (beats, beat_type) = time_signature.unwrap_or((4, 4));
+        let mut meta_track = vec![
             TrackEvent {
                 delta: 0.into(),
-                kind: TrackEventKind::Meta(MetaMessage::TimeSignature(4, 4, 24, 8)),
+                // the denominator is stored as a power of two
+                kind: TrackEventKind::Meta(MetaMessage::TimeSignature(
+                    beats,
+                    beat_type.ilog2() as u8,
+                    24,
+                    8,
+                )),
             },
             TrackEvent {
                 delta: 0.into(),
-                kind: TrackEventKind::Meta(MetaMessage::Tempo(LENGTH_OF_QUARTER.into())),
+                kind: TrackEventKind::Meta(MetaMessage::Tempo(length_of_quarter.into())),
             },
-            TrackEvent { delta: 0.into(), kind: TrackEventKind::Meta(MetaMessage::EndOfTrack) },
-        ]];
-        tracks.append(&mut midi_tracks);
+        ];
+        let texts = instruments.iter().map(|instrument| {
+            (instrument.texts.iter()).map(|(delta, event)| {
+                let message = match event {
+                    TextEvent::Marker(x) => MetaMessage::Marker(x.as_bytes()),
+                    TextEvent::Lyric(x) => MetaMessage::Lyric(x.as_bytes()),
+                };
+                TrackEvent { delta: (*delta).into(), kind: TrackEventKind::Meta(message) }
+            })
+        });
+        meta_track.extend(merge_tracks(texts.map(Iterator::collect).collect()));
+        let mut tracks = vec![meta_track];
+        // the channels used by the instruments before
+        let mut used = 0;
+        for (instrument, mut strings) in instruments.iter().zip(string_tracks) {
+            // a pitch bend applies to the whole channel, so every string of an instrument with bends
+            // gets a channel of its own, as long as there are enough of them left
+            let count = match instrument.bends && used + strings.len() <= MAX_CHANNELS {
+                true => strings.len(),
+                false => 1,
+            };
+            // channel 10 is for percussion
+            let channels: Vec<usize> =
+                (used..used + count).map(|x| if x < 9 { x } else { (x + 1).min(15) }).collect();
+            used += count;
+            let mut track = vec![];
+            if let Some(name) = &instrument.name {
+                let name = TrackEventKind::Meta(MetaMessage::TrackName(name.as_bytes()));
+                track.push(TrackEvent { delta: 0.into(), kind: name });
+            }
+            for (string, events) in strings.iter_mut().enumerate() {
+                if instrument.bends && string < count {
+                    events.splice(0..0, bend_range_events());
+                }
+                let channel = channels[string.min(count - 1)];
+                for event in events.iter_mut() {
+                    if let TrackEventKind::Midi { channel: c, .. } = &mut event.kind {
+                        *c = (channel as u8).into();
+                    }
+                }
+            }
+            track.extend(merge_tracks(strings));
+            tracks.push(track);
+        }
+        tracks.iter_mut().for_each(|x| {
+            x.push(TrackEvent {
+                delta: 0.into(),
+                kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+            })
+        });
         let smf = Smf {
-            header: Header::new(Format::Parallel, midly::Timing::Metrical(4.into())),
+            header: Header::new(Format::Parallel, midly::Timing::Metrical(RESOLUTION.into())),
             tracks,
         };
-        let gen_time = gen_start.elapsed();
-        let mut last_err = None;
-        if let Err(x) = smf.write_std(out) {
-            last_err = Some(x.into());
-        };
-        BackendResult::new(diagnostics, last_err, Some(parse_time), Some(gen_time))
+        Ok(smf.write_std(out)?)
     }
 }
 
-fn convert_to_midi(parsed: &ParserResult) -> Vec<Vec<TrackEvent<'static>>> {
-    // TODO: maybe use the traditional note resolving logic here?
-    let mut string_freq = HashMap::new();
-    string_freq.insert('E', 52);
-    string_freq.insert('A', 57);
-    string_freq.insert('D', 62);
-    string_freq.insert('G', 67);
-    string_freq.insert('B', 71);
-    string_freq.insert('d', 74);
-    string_freq.insert('e', 76);
-    let track_len = parsed.tick_stream.len() / 6;
-    // https://rust-lang.github.io/rust-clippy/master/index.html#repeat_vec_with_capacity
-    let mut tracks: Vec<Vec<TrackEvent>> =
-        iter::repeat_with(|| Vec::with_capacity(track_len)).take(6).collect();
-    let mut delta_carry_on = [u28::new(0); 6];
-    for (event_idx, event) in parsed.tick_stream.iter().enumerate() {
-        // TODO: eventually try to interpolate for slurred decorators
-        let track = event_idx % 6;
-        match &event {
-            Fret(fret) => {
-                let string_name = parsed.base_notes[track];
-                let pitch = fret + string_freq[&string_name];
-                let (note_on, note_off) = gen_note_events(pitch.into(), delta_carry_on[track]);
-                delta_carry_on[track] = 0.into();
-                tracks[track].push(note_on);
-                tracks[track].push(note_off);
+/// Merges tracks that are played at the same time into one. Events at the same time keep the
+/// order of their tracks, except that notes are stopped before others start.
+fn merge_tracks(tracks: Vec<Vec<TrackEvent>>) -> Vec<TrackEvent> {
+    let is_note_off = |x: &TrackEvent| {
+        matches!(x.kind, TrackEventKind::Midi { message: MidiMessage::NoteOff { .. }, .. })
+    };
+    let mut events = vec![];
+    for track in tracks {
+        let mut time = 0;
+        for event in track {
+            time += event.delta.as_int();
+            events.push((time, !is_note_off(&event), event));
+        }
+    }
+    events.sort_by_key(|x| (x.0, x.1));
+    let mut last = 0;
+    (events.into_iter())
+        .map(|(time, _, event)| {
+            let delta = time - last;
+            last = time;
+            TrackEvent { delta: delta.into(), ..event }
+        })
+        .collect()
+}
+
+impl InstrumentTracks {
+    fn push_notes(&mut self, parsed: &ParserResult) {
+        let n = parsed.string_count;
+        let track_len = parsed.tick_stream.len().checked_div(n).unwrap_or(0);
+        if self.tracks.len() < n {
+            // https://rust-lang.github.io/rust-clippy/master/index.html#repeat_vec_with_capacity
+            self.tracks.resize_with(n, || Vec::with_capacity(track_len));
+            self.delta_carry_on.resize(n, u28::new(0));
+        }
+        let (tracks, delta_carry_on) = (&mut self.tracks, &mut self.delta_carry_on);
+        let mut has_bends = false;
+        let mut palm_muted = vec![false; track_len];
+        for annotation in parsed.annotations.iter().filter(|x| x.kind == AnnotationKind::PalmMute) {
+            let (start, end) =
+                (*annotation.ticks.start() as usize, *annotation.ticks.end() as usize);
+            palm_muted[start..=end.min(track_len - 1)].fill(true);
+        }
+        let played = playback_order(&parsed.measures).into_iter().flat_map(|m| {
+            let range = &parsed.measures[m].data_range;
+            (*range.start() as usize..=*range.end() as usize).map(|x| (x, *range.end() as usize))
+        });
+        // the ticks of each string that were already played as part of a bent note
+        let mut bent_ticks = vec![0; n];
+        for (event_idx, measure_end) in played {
+            let event = &parsed.tick_stream[event_idx];
+            // TODO: eventually try to interpolate for slurred decorators
+            let track = event_idx % n;
+            if bent_ticks[track] > 0 {
+                bent_ticks[track] -= 1;
+                continue;
+            }
+            let duration = parsed.rhythm[event_idx / n].map(|x| x.duration()).unwrap_or(0);
+            match &event {
+                Fret(..)
+                | TabElement::NaturalHarmonic(..)
+                | TabElement::ArtificialHarmonic { .. }
+                | TabElement::GhostNote(..) => {
+                    let offset = event.sounding_offset().unwrap_or(0);
+                    let open_string =
+                        parsed.open_string_pitch(track).saturating_add(WRITTEN_OCTAVE);
+                    let pitch = open_string.saturating_add(offset).min(127);
+                    let (mut length, mut vel) = match palm_muted[event_idx / n] {
+                        true => (duration / 2, PALM_MUTE_VELOCITY),
+                        false => (duration, VELOCITY),
+                    };
+                    if let TabElement::GhostNote(..) = event {
+                        vel = vel.min(GHOST_NOTE_VELOCITY);
+                    }
+                    // accents and staccato are written after the note they apply to
+                    match parsed.tick_stream.get(event_idx + n) {
+                        Some(TabElement::Accent) => vel = vel.max(ACCENT_VELOCITY),
+                        Some(TabElement::Staccato) => length /= 2,
+                        _ => (),
+                    }
+                    let (bends, ticks) = bends_after(parsed, event_idx, measure_end, offset);
+                    if !bends.is_empty() {
+                        (has_bends, bent_ticks[track]) = (true, ticks);
+                        let events = gen_bent_note_events(
+                            pitch.into(),
+                            delta_carry_on[track],
+                            length,
+                            &bends,
+                            vel.into(),
+                        );
+                        tracks[track].extend(events);
+                        // a shortened note bends sooner, and the string waits out the rest
+                        delta_carry_on[track] = (duration - length).into();
+                        continue;
+                    }
+                    // trills and tremolos are written after the note they apply to
+                    let (count, interval) = match parsed.tick_stream.get(event_idx + n) {
+                        Some(TabElement::Trill) => (ORNAMENT_NOTES, TRILL_INTERVAL),
+                        Some(TabElement::Tremolo { .. }) => (ORNAMENT_NOTES, 0),
+                        _ => (1, 0),
+                    };
+                    let note_length = length / count;
+                    for i in 0..count {
+                        let key = if i % 2 == 1 {
+                            pitch.saturating_add(interval).min(127)
+                        } else {
+                            pitch
+                        };
+                        let (note_on, note_off) = gen_note_events(
+                            key.into(),
+                            delta_carry_on[track],
+                            note_length,
+                            vel.into(),
+                        );
+                        delta_carry_on[track] = 0.into();
+                        tracks[track].push(note_on);
+                        tracks[track].push(note_off);
+                    }
+                    delta_carry_on[track] = (duration - note_length * count).into();
+                }
+                // techniques are played along with the note before them, but their ticks still
+                // take time like rests, so that the string stays in step with the others
+                TabElement::Rest
+                | TabElement::Bend { .. }
+                | TabElement::HammerOn
+                | TabElement::Pull
+                | TabElement::Release { .. }
+                | TabElement::Slide { .. }
+                | TabElement::DeadNote
+                | TabElement::Vibrato
+                | TabElement::Tap
+                | TabElement::Trill
+                | TabElement::Tremolo { .. }
+                | TabElement::Accent
+                | TabElement::Staccato => delta_carry_on[track] += duration.into(),
+            }
+        }
+        self.bends |= has_bends;
+    }
+    /// Places the chord names and lyrics on the ticks they belong to, every time that tick is
+    /// played.
+    fn push_texts(&mut self, parsed: &ParserResult) {
+        let n = parsed.string_count as u32;
+        // the name of a section comes before the chord it starts with
+        let sections = parsed.sections.iter().map(|(measure, name)| {
+            let tick = parsed.measures[*measure as usize].data_range.start() / n;
+            (tick, TextEvent::Marker(name.clone()))
+        });
+        let markers = parsed.chords.iter().map(|x| (x.0, TextEvent::Marker(x.1.to_string())));
+        // syllables that continue in the next one end with a hyphen, as is usual in karaoke files
+        let lyrics = parsed.lyrics.iter().map(|(tick, x)| match x.syllabic {
+            Syllabic::Begin | Syllabic::Middle => (*tick, TextEvent::Lyric(format!("{}-", x.text))),
+            Syllabic::Single | Syllabic::End => (*tick, TextEvent::Lyric(x.text.clone())),
+        });
+        let mut events: Vec<_> = sections.chain(markers).chain(lyrics).collect();
+        events.sort_by_key(|x| x.0);
+        for measure in playback_order(&parsed.measures) {
+            let range = &parsed.measures[measure].data_range;
+            for tick in (*range.start()..=*range.end()).step_by(n as usize).map(|x| x / n) {
+                let first = events.partition_point(|x| x.0 < tick);
+                for (_, event) in events[first..].iter().take_while(|x| x.0 == tick) {
+                    self.texts.push((self.text_delta, event.clone()));
+                    self.text_delta = 0;
+                }
+                self.text_delta += parsed.rhythm[tick as usize].map(|x| x.duration()).unwrap_or(0);
             }
-            TabElement::Rest => delta_carry_on[track] += LENGTH_OF_EIGHTH.into(),
-            TabElement::Bend
-            | TabElement::HammerOn
-            | TabElement::Pull
-            | TabElement::Release
-            | TabElement::Slide
-            | TabElement::DeadNote
-            | TabElement::Vibrato => (),
         }
     }
-    tracks.iter_mut().for_each(|x| {
-        x.push(TrackEvent { delta: 0.into(), kind: TrackEventKind::Meta(MetaMessage::EndOfTrack) })
-    });
-    tracks
 }
 
-fn gen_note_events<'a>(key: u7, initial_delta: u28) -> (TrackEvent<'a>, TrackEvent<'a>) {
+fn gen_note_events<'a>(
+    key: u7, initial_delta: u28, length: u32, vel: u7,
+) -> (TrackEvent<'a>, TrackEvent<'a>) {
     let note_on = TrackEvent {
         delta: initial_delta,
+        kind: TrackEventKind::Midi { channel: 0.into(), message: MidiMessage::NoteOn { key, vel } },
+    };
+
+    let note_off = TrackEvent {
+        delta: length.into(),
         kind: TrackEventKind::Midi {
             channel: 0.into(),
-            message: MidiMessage::NoteOn { key, vel: 100.into() },
+            message: MidiMessage::NoteOff { key, vel },
         },
     };
+    (note_on, note_off)
+}
 
-    let note_off = TrackEvent {
-        delta: LENGTH_OF_EIGHTH.into(),
+/// The bends and releases after the note at `note_idx` on its string, up to `last_idx`, and the
+/// number of ticks they take including the rests between them. `offset` is the note above the
+/// open string. Like in the score, there can be rests before a release, but not before a bend.
+fn bends_after(
+    parsed: &ParserResult, note_idx: usize, last_idx: usize, offset: u8,
+) -> (Vec<Bend>, usize) {
+    let n = parsed.string_count;
+    let mut bends = vec![];
+    let (mut current, mut ticks) = (0, 0);
+    // the rests since the last bend, and their time
+    let (mut rests, mut hold) = (0, 0);
+    for idx in (note_idx + n..=last_idx).step_by(n) {
+        let elem = &parsed.tick_stream[idx];
+        let duration = parsed.rhythm[idx / n].map(|x| x.duration()).unwrap_or(0);
+        match elem {
+            TabElement::Rest if !bends.is_empty() => {
+                (rests, hold) = (rests + 1, hold + duration);
+                continue;
+            }
+            TabElement::Release { .. } => {}
+            TabElement::Bend { .. } if rests == 0 => {}
+            _ => break,
+        }
+        let Some(target) = elem.bend_target(offset, current) else { break };
+        // only a pre-bend right on the note is played before it
+        let pre = bends.is_empty() && matches!(elem, TabElement::Bend { pre: true, .. });
+        bends.push(Bend { target, hold, duration, pre });
+        (current, ticks) = (target, ticks + rests + 1);
+        (rests, hold) = (0, 0);
+    }
+    (bends, ticks)
+}
+
+/// A bend or a release of a note
+struct Bend {
+    /// In semitones above the note
+    target: u8,
+    /// How long the pitch is held before the bend
+    hold: u32,
+    duration: u32,
+    pre: bool,
+}
+
+/// A pitch bend to `pitch`, which is in [BEND_STEPS]ths of a semitone
+fn gen_pitch_bend<'a>(delta: u32, pitch: i32) -> TrackEvent<'a> {
+    let range = BEND_RANGE as i32 * BEND_STEPS as i32;
+    let bend = (pitch * 0x2000 / range).clamp(-0x2000, 0x1fff) as i16;
+    TrackEvent {
+        delta: delta.into(),
+        kind: TrackEventKind::Midi {
+            channel: 0.into(),
+            message: MidiMessage::PitchBend { bend: PitchBend::from_int(bend) },
+        },
+    }
+}
+
+/// A note that is held for `length` and then through all of its `bends`. Every bend glides to
+/// its pitch over its duration, and the pitch is reset after the note.
+fn gen_bent_note_events<'a>(
+    key: u7, initial_delta: u28, mut length: u32, bends: &[Bend], vel: u7,
+) -> Vec<TrackEvent<'a>> {
+    let mut events = vec![];
+    let (mut delta, mut current) = (initial_delta.as_int(), 0);
+    let mut bends = bends.iter().peekable();
+    if let Some(pre) = bends.next_if(|x| x.pre) {
+        current = pre.target as i32 * BEND_STEPS as i32;
+        events.push(gen_pitch_bend(delta, current));
+        delta = 0;
+        // the pre-bend is held like the note
+        length += pre.duration;
+    }
+    let (note_on, note_off) = gen_note_events(key, delta.into(), 0, vel);
+    events.push(note_on);
+    delta = length;
+    for bend in bends {
+        let target = bend.target as i32 * BEND_STEPS as i32;
+        delta += bend.hold;
+        for i in 1..=BEND_STEPS {
+            let at = |i: u32| bend.duration * i / BEND_STEPS;
+            delta += at(i) - at(i - 1);
+            let pitch = current + (target - current) * i as i32 / BEND_STEPS as i32;
+            events.push(gen_pitch_bend(delta, pitch));
+            delta = 0;
+        }
+        current = target;
+    }
+    events.push(TrackEvent { delta: delta.into(), ..note_off });
+    events.push(gen_pitch_bend(0, 0));
+    events
+}
+
+/// Sets the pitch bend range to [BEND_RANGE], with the registered parameter 0
+fn bend_range_events<'a>() -> [TrackEvent<'a>; 4] {
+    let controller = |controller: u8, value: u8| TrackEvent {
+        delta: 0.into(),
         kind: TrackEventKind::Midi {
             channel: 0.into(),
-            message: MidiMessage::NoteOff { key, vel: 100.into() },
+            message: MidiMessage::Controller { controller: controller.into(), value: value.into() },
         },
     };
-    (note_on, note_off)
+    [controller(101, 0), controller(100, 0), controller(6, BEND_RANGE), controller(38, 0)]
 }
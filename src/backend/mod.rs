@@ -2,23 +2,24 @@ use errors::{backend_error::BackendError, diagnostic::Diagnostic};
 
 use std::{fmt::Display, time::Duration};
 
-use crate::BufLines;
+use crate::{parser::settings::ParserSettings, BufLines};
 pub mod errors;
 pub mod fixup;
 pub mod midi;
 pub mod muxml;
 pub struct BackendResult {
     pub diagnostics: Vec<Diagnostic>,
-    pub err: Option<BackendError>,
+    /// Every error found. The parser only keeps going after the first one when it recovers.
+    pub errors: Vec<BackendError>,
     pub timing_parse: Option<Duration>,
     pub timing_gen: Option<Duration>,
 }
 impl BackendResult {
     pub fn new(
-        diagnostics: Vec<Diagnostic>, err: Option<BackendError>, timing_parse: Option<Duration>,
+        diagnostics: Vec<Diagnostic>, errors: Vec<BackendError>, timing_parse: Option<Duration>,
         timing_gen: Option<Duration>,
     ) -> Self {
-        Self { diagnostics, err, timing_parse, timing_gen }
+        Self { diagnostics, errors, timing_parse, timing_gen }
     }
 }
 pub trait Backend {
@@ -26,6 +27,14 @@ pub trait Backend {
 
     fn process<Out: std::io::Write>(
         input: &BufLines, out: &mut Out, settings: Self::BackendSettings,
+    ) -> BackendResult {
+        Self::process_with(input, ParserSettings::default(), out, settings)
+    }
+
+    /// Like [Backend::process], but lets the caller configure the parser.
+    fn process_with<Out: std::io::Write>(
+        input: &BufLines, parser_settings: ParserSettings, out: &mut Out,
+        settings: Self::BackendSettings,
     ) -> BackendResult;
 }
 
@@ -47,10 +56,20 @@ pub enum BackendSelector {
 
 impl BackendSelector {
     pub fn process<Out: std::io::Write>(self, input: &BufLines, out: &mut Out) -> BackendResult {
+        self.process_with(input, ParserSettings::default(), out)
+    }
+    pub fn process_with<Out: std::io::Write>(
+        self, input: &BufLines, parser_settings: ParserSettings, out: &mut Out,
+    ) -> BackendResult {
+        let ps = parser_settings;
         match self {
-            BackendSelector::Midi => midi::MidiBackend::process(input, out, ()),
-            BackendSelector::Muxml(settings) => muxml::MuxmlBackend::process(input, out, settings),
-            BackendSelector::Fixup(settings) => fixup::FixupBackend::process(input, out, settings),
+            BackendSelector::Midi => midi::MidiBackend::process_with(input, ps, out, ()),
+            BackendSelector::Muxml(settings) => {
+                muxml::MuxmlBackend::process_with(input, ps, out, settings)
+            }
+            BackendSelector::Fixup(settings) => {
+                fixup::FixupBackend::process_with(input, ps, out, settings)
+            }
         }
     }
 }
@@ -1,4 +1,7 @@
 use crate::backend::muxml::{NoteProperties, Vibrato};
+use crate::parser::chord::Chord;
+use crate::parser::metadata::ScoreMetadata;
+use crate::parser::rhythm::{Rhythm, PER_QUARTER};
 use itoa::Buffer;
 use tracing::debug;
 // This file uses explicit .write_str() -s, instead of writing a format!()ted string, because I
@@ -6,29 +9,68 @@ use tracing::debug;
 // Maybe there is a nice solution to this - but I've yet to find anything as performant as this one.
 #[inline]
 pub fn write_muxml2_rest(
-    buf: &mut impl std::fmt::Write, r#type: &str, duration: u8,
+    buf: &mut impl std::fmt::Write, rhythm: Rhythm, divisions: u32,
 ) -> Result<(), std::fmt::Error> {
     buf.write_str(
         r#"<note>
 <rest measure="no"/>
-<duration>"#,
+"#,
     )?;
+    write_duration(buf, rhythm, divisions)?;
+    buf.write_str("<voice>1</voice>\n")?;
+    write_type(buf, rhythm)?;
+    write_time_modification(buf, rhythm)?;
+    buf.write_str("</note>\n")?;
+    Ok(())
+}
+
+/// `<duration>`, where a quarter is `divisions` long
+#[inline]
+fn write_duration(
+    buf: &mut impl std::fmt::Write, rhythm: Rhythm, divisions: u32,
+) -> std::fmt::Result {
     let mut dbuf = Buffer::new();
-    buf.write_str(dbuf.format(duration))?;
-    buf.write_str(
-        r#"</duration>
-<voice>1</voice>
-<type>"#,
-    )?;
-    buf.write_str(r#type)?;
-    buf.write_str("</type>\n</note>\n")?;
+    buf.write_str("<duration>")?;
+    buf.write_str(dbuf.format(rhythm.duration() * divisions / PER_QUARTER))?;
+    buf.write_str("</duration>\n")
+}
+
+/// `<type>` and the dots after it
+#[inline]
+fn write_type(buf: &mut impl std::fmt::Write, rhythm: Rhythm) -> std::fmt::Result {
+    buf.write_str("<type>")?;
+    buf.write_str(rhythm.value.name())?;
+    buf.write_str("</type>\n")?;
+    for _ in 0..rhythm.dots {
+        buf.write_str("<dot/>\n")?;
+    }
     Ok(())
 }
 
 #[inline]
+fn write_time_modification(buf: &mut impl std::fmt::Write, rhythm: Rhythm) -> std::fmt::Result {
+    if rhythm.triplet {
+        buf.write_str(
+            "<time-modification><actual-notes>3</actual-notes><normal-notes>2</normal-notes></time-modification>\n",
+        )?;
+    }
+    Ok(())
+}
+
+/// How a note is played, which decides its notehead and technical notations
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NoteKind {
+    Normal,
+    Dead,
+    Ghost,
+    NaturalHarmonic,
+    ArtificialHarmonic,
+}
+#[inline]
+#[allow(clippy::too_many_arguments)]
 pub fn write_muxml2_note(
-    buf: &mut impl std::fmt::Write, step: char, octave: u8, sharp: bool, chord: bool, dead: bool,
-    properties: Option<&NoteProperties>,
+    buf: &mut impl std::fmt::Write, step: char, octave: u8, sharp: bool, chord: bool,
+    kind: NoteKind, rhythm: Rhythm, divisions: u32, properties: Option<&NoteProperties>,
 ) -> Result<(), std::fmt::Error> {
     let mut nbuf = itoa::Buffer::new();
     buf.write_str("<note>\n")?;
@@ -43,24 +85,77 @@ pub fn write_muxml2_note(
     }
     buf.write_str("<octave>")?;
     buf.write_str(nbuf.format(octave))?;
-    buf.write_str(
-        r#"</octave>
-</pitch>
-<duration>1</duration>
-<type>eighth</type>
-"#,
-    )?;
+    buf.write_str("</octave>\n</pitch>\n")?;
+    write_duration(buf, rhythm, divisions)?;
+    write_type(buf, rhythm)?;
     if sharp {
         buf.write_str("<accidental>sharp</accidental>\n")?;
     }
-    if dead {
-        buf.write_str("<notehead>x</notehead>\n")?;
+    write_time_modification(buf, rhythm)?;
+    match kind {
+        NoteKind::Normal => (),
+        NoteKind::Dead => buf.write_str("<notehead>x</notehead>\n")?,
+        NoteKind::Ghost => buf.write_str("<notehead parentheses=\"yes\">normal</notehead>\n")?,
+        NoteKind::NaturalHarmonic | NoteKind::ArtificialHarmonic => {
+            buf.write_str("<notehead>diamond</notehead>\n")?
+        }
+    }
+    let harmonic = match kind {
+        NoteKind::NaturalHarmonic => Some("<natural/>"),
+        NoteKind::ArtificialHarmonic => Some("<artificial/>"),
+        NoteKind::Normal | NoteKind::Dead | NoteKind::Ghost => None,
+    };
+    let notations = properties.is_some_and(NoteProperties::has_notations);
+    if notations || harmonic.is_some() {
+        buf.write_str("<notations>\n")?;
+    }
+    let tap = properties.is_some_and(|x| x.tap);
+    let bends = properties.map(|x| x.bends.as_slice()).unwrap_or_default();
+    if harmonic.is_some() || tap || !bends.is_empty() {
+        buf.write_str("<technical>\n")?;
+        if let Some(harmonic) = harmonic {
+            buf.write_str("<harmonic>")?;
+            buf.write_str(harmonic)?;
+            buf.write_str("</harmonic>\n")?;
+        }
+        for bend in bends {
+            buf.write_str("<bend><bend-alter>")?;
+            buf.write_str(nbuf.format(bend.alter))?;
+            buf.write_str("</bend-alter>")?;
+            if bend.pre {
+                buf.write_str("<pre-bend/>")?;
+            } else if bend.release {
+                buf.write_str("<release/>")?;
+            }
+            buf.write_str("</bend>\n")?;
+        }
+        if tap {
+            buf.write_str("<tap/>\n")?;
+        }
+        buf.write_str("</technical>\n")?;
+    }
+    let slides = properties.map(|x| [x.slide_in, x.slide_out]).unwrap_or_default();
+    let accent = properties.is_some_and(|x| x.accent);
+    let staccato = properties.is_some_and(|x| x.staccato);
+    if slides.iter().any(Option::is_some) || accent || staccato {
+        buf.write_str("<articulations>\n")?;
+        if accent {
+            buf.write_str("<accent/>\n")?;
+        }
+        if staccato {
+            buf.write_str("<staccato/>\n")?;
+        }
+        for slide in slides.into_iter().flatten() {
+            buf.write_char('<')?;
+            buf.write_str(slide.name())?;
+            buf.write_str("/>\n")?;
+        }
+        buf.write_str("</articulations>\n")?;
     }
     match properties {
         None => (),
-        Some(NoteProperties { slurs, slide, vibrato }) => {
+        Some(NoteProperties { slurs, slide, vibrato, trill, tremolo, .. }) => {
             debug!(?slurs, "slurs");
-            buf.write_str("<notations>\n")?;
             for slur in slurs {
                 buf.write_str(r#"<slur type=""#)?;
                 buf.write_str(if slur.start { "start" } else { "stop" })?;
@@ -75,34 +170,238 @@ pub fn write_muxml2_note(
                 buf.write_str(nbuf.format(slide.number))?;
                 buf.write_str("\" />\n")?;
             }
-            if let Some(vibrato) = vibrato {
+            if vibrato.is_some() || *trill || *tremolo {
                 buf.write_str("<ornaments>\n")?;
-                buf.write_str("<wavy-line type=\"")?;
-                buf.write_str(if matches!(vibrato, Vibrato::Start) { "start" } else { "stop" })?;
-                buf.write_str("\" />\n")?;
+                if *trill {
+                    buf.write_str("<trill-mark/>\n")?;
+                }
+                if let Some(vibrato) = vibrato {
+                    buf.write_str("<wavy-line type=\"")?;
+                    buf.write_str(if matches!(vibrato, Vibrato::Start) {
+                        "start"
+                    } else {
+                        "stop"
+                    })?;
+                    buf.write_str("\" />\n")?;
+                }
+                if *tremolo {
+                    buf.write_str("<tremolo type=\"single\">3</tremolo>\n")?;
+                }
                 buf.write_str("</ornaments>\n")?;
             }
-            buf.write_str("</notations>\n")?;
         }
     }
+    if notations || harmonic.is_some() {
+        buf.write_str("</notations>\n")?;
+    }
+    if let Some(lyric) = properties.and_then(|x| x.lyric.as_ref()) {
+        buf.write_str("<lyric number=\"1\">\n<syllabic>")?;
+        buf.write_str(lyric.syllabic.name())?;
+        buf.write_str("</syllabic>\n<text>")?;
+        write_escaped(buf, &lyric.text)?;
+        buf.write_str("</text>\n</lyric>\n")?;
+    }
     buf.write_str("</note>\n")?;
     Ok(())
 }
 
+/// Writes `text` with the characters that are special in XML escaped.
+pub fn write_escaped(buf: &mut impl std::fmt::Write, text: &str) -> Result<(), std::fmt::Error> {
+    for c in text.chars() {
+        match c {
+            '&' => buf.write_str("&amp;")?,
+            '<' => buf.write_str("&lt;")?,
+            '>' => buf.write_str("&gt;")?,
+            '"' => buf.write_str("&quot;")?,
+            c => buf.write_char(c)?,
+        }
+    }
+    Ok(())
+}
+
+/// Writes everything before the first part, with a `<score-part>` for every part name.
+pub fn write_muxml2_document_prelude<'a>(
+    buf: &mut impl std::fmt::Write, metadata: &ScoreMetadata,
+    part_names: impl Iterator<Item = &'a str>,
+) -> Result<(), std::fmt::Error> {
+    buf.write_str(MUXML_DOCUMENT_START)?;
+    if let Some(title) = &metadata.title {
+        buf.write_str("  <work>\n    <work-title>")?;
+        write_escaped(buf, title)?;
+        buf.write_str("</work-title>\n  </work>\n")?;
+    }
+    buf.write_str("  <identification>\n")?;
+    if let Some(artist) = &metadata.artist {
+        buf.write_str(r#"    <creator type="composer">"#)?;
+        write_escaped(buf, artist)?;
+        buf.write_str("</creator>\n")?;
+    }
+    buf.write_str(MUXML_INCOMPLETE_DOC_PRELUDE)?;
+    let mut nbuf = Buffer::new();
+    buf.write_str("  <part-list>\n")?;
+    for (idx, name) in part_names.enumerate() {
+        buf.write_str("    <score-part id=\"P")?;
+        buf.write_str(nbuf.format(idx + 1))?;
+        buf.write_str("\">\n      <part-name>")?;
+        write_escaped(buf, name)?;
+        buf.write_str("</part-name>\n    </score-part>\n")?;
+    }
+    buf.write_str("  </part-list>\n")
+}
+
+/// The start of the `idx`th part, counted from zero
+pub fn write_muxml2_part_start(buf: &mut impl std::fmt::Write, idx: usize) -> std::fmt::Result {
+    let mut nbuf = Buffer::new();
+    buf.write_str("  <part id=\"P")?;
+    buf.write_str(nbuf.format(idx + 1))?;
+    buf.write_str("\">\n")
+}
+
+#[inline]
+pub fn write_muxml2_tempo(buf: &mut impl std::fmt::Write, tempo: u32) -> std::fmt::Result {
+    let mut nbuf = Buffer::new();
+    buf.write_str(
+        r#"<direction placement="above">
+<direction-type><metronome><beat-unit>quarter</beat-unit><per-minute>"#,
+    )?;
+    buf.write_str(nbuf.format(tempo))?;
+    buf.write_str(
+        r#"</per-minute></metronome></direction-type>
+<sound tempo=""#,
+    )?;
+    buf.write_str(nbuf.format(tempo))?;
+    buf.write_str("\"/>\n</direction>\n")
+}
+
+/// The name of a section of the score, like `Verse`
+pub fn write_muxml2_rehearsal(buf: &mut impl std::fmt::Write, name: &str) -> std::fmt::Result {
+    buf.write_str("<direction placement=\"above\">\n<direction-type><rehearsal>")?;
+    write_escaped(buf, name)?;
+    buf.write_str("</rehearsal></direction-type>\n</direction>\n")
+}
+
+/// Text written next to the music, like `let ring` after the end of a line
+pub fn write_muxml2_words(buf: &mut impl std::fmt::Write, text: &str) -> std::fmt::Result {
+    buf.write_str("<direction placement=\"above\">\n<direction-type><words>")?;
+    write_escaped(buf, text)?;
+    buf.write_str("</words></direction-type>\n</direction>\n")
+}
+
+/// A chord symbol
+pub fn write_muxml2_harmony(buf: &mut impl std::fmt::Write, chord: &Chord) -> std::fmt::Result {
+    let mut nbuf = Buffer::new();
+    buf.write_str("<harmony>\n<root><root-step>")?;
+    buf.write_char(chord.root.step)?;
+    buf.write_str("</root-step>")?;
+    if chord.root.alter != 0 {
+        buf.write_str("<root-alter>")?;
+        buf.write_str(nbuf.format(chord.root.alter))?;
+        buf.write_str("</root-alter>")?;
+    }
+    buf.write_str("</root>\n<kind")?;
+    if !chord.suffix.is_empty() {
+        buf.write_str(" text=\"")?;
+        write_escaped(buf, chord.suffix)?;
+        buf.write_str("\"")?;
+    }
+    buf.write_str(">")?;
+    buf.write_str(chord.kind)?;
+    buf.write_str("</kind>\n")?;
+    if let Some(bass) = chord.bass {
+        buf.write_str("<bass><bass-step>")?;
+        buf.write_char(bass.step)?;
+        buf.write_str("</bass-step>")?;
+        if bass.alter != 0 {
+            buf.write_str("<bass-alter>")?;
+            buf.write_str(nbuf.format(bass.alter))?;
+            buf.write_str("</bass-alter>")?;
+        }
+        buf.write_str("</bass>\n")?;
+    }
+    buf.write_str("</harmony>\n")
+}
+
+/// The start of a technique written above a span of notes, like `P.M. - - -`.
+pub fn write_muxml2_dashes_start(
+    buf: &mut impl std::fmt::Write, label: &str, number: usize,
+) -> std::fmt::Result {
+    let mut nbuf = Buffer::new();
+    buf.write_str("<direction placement=\"above\">\n<direction-type><words>")?;
+    write_escaped(buf, label)?;
+    buf.write_str("</words></direction-type>\n<direction-type><dashes type=\"start\" number=\"")?;
+    buf.write_str(nbuf.format(number))?;
+    buf.write_str("\"/></direction-type>\n</direction>\n")
+}
+
+pub fn write_muxml2_dashes_stop(buf: &mut impl std::fmt::Write, number: usize) -> std::fmt::Result {
+    let mut nbuf = Buffer::new();
+    buf.write_str(
+        "<direction placement=\"above\">\n<direction-type><dashes type=\"stop\" number=\"",
+    )?;
+    buf.write_str(nbuf.format(number))?;
+    buf.write_str("\"/></direction-type>\n</direction>\n")
+}
+
+/// The barline at the start of a measure, for a forward repeat and/or the start of an ending.
+pub fn write_muxml2_left_barline(
+    buf: &mut impl std::fmt::Write, repeat: bool, ending: Option<u8>,
+) -> std::fmt::Result {
+    let mut nbuf = Buffer::new();
+    buf.write_str("<barline location=\"left\">\n")?;
+    if repeat {
+        buf.write_str("<bar-style>heavy-light</bar-style>\n")?;
+    }
+    if let Some(ending) = ending {
+        buf.write_str(r#"<ending number=""#)?;
+        buf.write_str(nbuf.format(ending))?;
+        buf.write_str("\" type=\"start\"/>\n")?;
+    }
+    if repeat {
+        buf.write_str("<repeat direction=\"forward\"/>\n")?;
+    }
+    buf.write_str("</barline>\n")
+}
+
+/// The barline at the end of a measure, for a backward repeat and/or the end of an ending.
+pub fn write_muxml2_right_barline(
+    buf: &mut impl std::fmt::Write, repeat_times: Option<u8>, ending: Option<u8>,
+) -> std::fmt::Result {
+    let mut nbuf = Buffer::new();
+    buf.write_str("<barline location=\"right\">\n")?;
+    if repeat_times.is_some() {
+        buf.write_str("<bar-style>light-heavy</bar-style>\n")?;
+    }
+    if let Some(ending) = ending {
+        buf.write_str(r#"<ending number=""#)?;
+        buf.write_str(nbuf.format(ending))?;
+        // the last ending isn't followed by a repeat, so it has no closing hook
+        buf.write_str(if repeat_times.is_some() {
+            "\" type=\"stop\""
+        } else {
+            "\" type=\"discontinue\""
+        })?;
+        buf.write_str("/>\n")?;
+    }
+    if let Some(times) = repeat_times {
+        buf.write_str(r#"<repeat direction="backward" times=""#)?;
+        buf.write_str(nbuf.format(times))?;
+        buf.write_str("\"/>\n")?;
+    }
+    buf.write_str("</barline>\n")
+}
+
 #[inline]
 pub fn write_muxml2_measure_prelude(
     buf: &mut impl std::fmt::Write, number: usize, note_count: usize, note_type: usize,
+    divisions: u32, capo: u8,
 ) -> Result<(), std::fmt::Error> {
     let first_measure = number == 0;
     buf.write_str(r#"<measure number=""#)?;
     let mut nbuf = Buffer::new();
     buf.write_str(nbuf.format(number))?;
-    buf.write_str(
-        r#"">
-<attributes>
-<divisions>2</divisions>
-"#,
-    )?;
+    buf.write_str("\">\n<attributes>\n<divisions>")?;
+    buf.write_str(nbuf.format(divisions))?;
+    buf.write_str("</divisions>\n")?;
     if first_measure {
         buf.write_str("<key><fifths>0</fifths></key>\n")?
     };
@@ -114,15 +413,21 @@ pub fn write_muxml2_measure_prelude(
     if first_measure {
         buf.write_str("<clef><sign>G</sign><line>2</line></clef>\n")?
     }
+    if first_measure && capo != 0 {
+        buf.write_str("<staff-details><capo>")?;
+        buf.write_str(nbuf.format(capo))?;
+        buf.write_str("</capo></staff-details>\n")?;
+    }
     buf.write_str("</attributes>\n")?;
     Ok(())
 }
-pub const MUXML_INCOMPLETE_DOC_PRELUDE: &str = r#"
+pub const MUXML_DOCUMENT_START: &str = r#"
 <?xml version="1.0" encoding="UTF-8"?>
 <!DOCTYPE score-partwise PUBLIC "-//Recordare//DTD MusicXML 4.0 Partwise//EN" "http://www.musicxml.org/dtds/partwise.dtd">
 <score-partwise version="4.0">
-  <identification>
-    <encoding>
+"#;
+/// Continues the `<identification>` element opened by [write_muxml2_document_prelude]
+pub const MUXML_INCOMPLETE_DOC_PRELUDE: &str = r#"    <encoding>
       <software>scoreman</software>
       <supports element="accidental" type="yes"/>
       <supports element="beam" type="yes"/>
@@ -131,14 +436,6 @@ pub const MUXML_INCOMPLETE_DOC_PRELUDE: &str = r#"
       <supports element="stem" type="yes"/>
     </encoding>
   </identification>
-  <part-list>
-    <score-part id="P1">
-      <part-name>Guitar1</part-name>
-    </score-part>
-  </part-list>
-  <part id="P1">
-"#;
-pub const MUXML2_DOCUMENT_END: &str = r#"
-</part>
-</score-partwise>
 "#;
+pub const MUXML2_PART_END: &str = "\n</part>\n";
+pub const MUXML2_DOCUMENT_END: &str = "</score-partwise>\n";
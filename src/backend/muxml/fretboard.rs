@@ -1,5 +1,3 @@
-use std::num::NonZeroU8;
-
 const NOTE2_STEPS: [(char, bool); 12] = [
     ('C', false),
     ('C', true),
@@ -35,15 +33,8 @@ impl MuxmlNote2 {
     }
 }
 
-// TODO: make this panic an error
-pub fn get_fretboard_note2(string: char, fret: u8) -> Option<MuxmlNote2> {
-    let base = (*STRING_BASE_NOTES.get(string as usize)?)?;
-    Some(MuxmlNote2 { step: base.get() + fret, dead: false })
+/// `open_string` is the MIDI note number of the string. Guitar music is written an octave higher
+/// than it sounds, which is exactly what using MIDI numbers as steps gives us.
+pub fn get_fretboard_note2(open_string: u8, fret: u8) -> MuxmlNote2 {
+    MuxmlNote2 { step: open_string.saturating_add(fret), dead: false }
 }
-
-/// TODO: benchmark this against a match and a hashmap, a hashmap will be probably faster as it can
-/// stay in cache (we need a total of max 16 base notes*1 byte => 16bytes which is less than a
-/// cache line
-/// Generated by tools/gen_note_lookup.rs
-#[rustfmt::skip]
-const STRING_BASE_NOTES: [Option<NonZeroU8>;128] = unsafe {[None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, Some(NonZeroU8::new(45).unwrap_unchecked()), Some(NonZeroU8::new(59).unwrap_unchecked()), None, Some(NonZeroU8::new(50).unwrap_unchecked()), Some(NonZeroU8::new(40).unwrap_unchecked()), None, Some(NonZeroU8::new(55).unwrap_unchecked()), None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, Some(NonZeroU8::new(62).unwrap_unchecked()), Some(NonZeroU8::new(64).unwrap_unchecked()), None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None]};
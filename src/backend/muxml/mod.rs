@@ -3,17 +3,27 @@ pub mod fretboard;
 #[cfg(test)]
 mod muxml2_tests;
 pub mod settings;
-use crate::backend::errors::backend_error::BackendError;
+use crate::backend::errors::{
+    backend_error::BackendError, diagnostic::Diagnostic, diagnostic_kind::DiagnosticKind,
+    error_location::ErrorLocation,
+};
+use crate::parser::annotation::AnnotationKind;
+use crate::parser::lyrics::Syllable;
+use crate::parser::rhythm::{NoteValue, Rhythm, PER_QUARTER};
 use crate::parser::tab_element::TabElement;
-use crate::parser::{source_location_from_stream, Parser, ParserResult};
+use crate::parser::trailing::Trailing;
+use crate::parser::{settings::ParserSettings, source_location_from_stream, Parser, ParserResult};
 use crate::BufLines;
 use crate::{
     backend::{Backend, BackendResult},
     rlen, time,
 };
 use formatters::{
-    write_muxml2_measure_prelude, write_muxml2_note, write_muxml2_rest, MUXML2_DOCUMENT_END,
-    MUXML_INCOMPLETE_DOC_PRELUDE,
+    write_muxml2_dashes_start, write_muxml2_dashes_stop, write_muxml2_document_prelude,
+    write_muxml2_harmony, write_muxml2_left_barline, write_muxml2_measure_prelude,
+    write_muxml2_note, write_muxml2_part_start, write_muxml2_rehearsal, write_muxml2_rest,
+    write_muxml2_right_barline, write_muxml2_tempo, write_muxml2_words, NoteKind,
+    MUXML2_DOCUMENT_END, MUXML2_PART_END,
 };
 use fretboard::get_fretboard_note2;
 use rustc_hash::FxBuildHasher;
@@ -25,25 +35,45 @@ pub struct MuxmlBackend();
 impl Backend for MuxmlBackend {
     type BackendSettings = settings::Settings;
 
-    fn process<Out: std::io::Write>(
-        input: &BufLines, out: &mut Out, settings: Self::BackendSettings,
+    fn process_with<Out: std::io::Write>(
+        input: &BufLines, parser_settings: ParserSettings, out: &mut Out,
+        settings: Self::BackendSettings,
     ) -> BackendResult {
-        let (parse_time, parsed0) = time(|| Parser::parse(input));
-        let parsed = match parsed0 {
-            Ok(x) => x,
-            Err(y) => return BackendResult::new(vec![], Some(y.0), Some(parse_time), None),
-        };
+        let recover = parser_settings.recover;
+        let (parse_time, (instruments, errors)) =
+            time(|| Parser::parse_instruments(input, parser_settings));
+        let diagnostics = instruments.iter().flat_map(|x| x.diagnostics.iter().cloned()).collect();
+        if !errors.is_empty() && !recover {
+            return BackendResult::new(diagnostics, errors, Some(parse_time), None);
+        }
 
-        let generator = MuxmlGenerator::init(parsed, parse_time, settings);
-        let (gen_time, (xml_out, mut gen_result)) = time(|| generator.gen());
-        gen_result.timing_gen = Some(gen_time);
-        if gen_result.err.is_some() {
-            return gen_result;
+        // the errors the parser recovered from come before the ones of the generator
+        let mut r = BackendResult::new(diagnostics, errors, Some(parse_time), None);
+        let mut document = String::new();
+        let names = instruments.iter().map(|x| x.instrument.as_deref().unwrap_or("Guitar1"));
+        write_muxml2_document_prelude(&mut document, &instruments[0].metadata, names).unwrap();
+        let mut gen_time = Duration::ZERO;
+        for (idx, mut parsed) in instruments.into_iter().enumerate() {
+            // the tempo is only written above the first part
+            if idx > 0 {
+                parsed.metadata.tempo = None;
+            }
+            write_muxml2_part_start(&mut document, idx).unwrap();
+            let generator = MuxmlGenerator::init(parsed, document, settings.clone());
+            let (part_time, (xml_out, gen_result)) = time(|| generator.gen());
+            gen_time += part_time;
+            r.diagnostics.extend(gen_result.diagnostics);
+            r.errors.extend(gen_result.errors);
+            r.timing_gen = Some(gen_time);
+            let Some(xml_out) = xml_out else { return r };
+            document = xml_out;
+            document += MUXML2_PART_END;
         }
-        if let Err(x) = out.write_all(xml_out.unwrap().as_bytes()) {
-            gen_result.err = Some(x.into());
+        document += MUXML2_DOCUMENT_END;
+        if let Err(x) = out.write_all(document.as_bytes()) {
+            r.errors.push(x.into());
         }
-        gen_result
+        r
     }
 }
 
@@ -51,16 +81,20 @@ impl Backend for MuxmlBackend {
 pub enum Muxml2TabElement {
     Rest(u32),
     CopyTick(u32),
+    /// A rest tick with a length from a rhythm line, stored as its index in the tick stream
+    TimedRest(u32),
     /// used in optimizing, should generate no code for this type
     Invalid,
 }
 
+/// Writes `x` eighths of rest
 #[inline(always)]
-fn write_rest(buf: &mut impl std::fmt::Write, mut x: u32) -> std::fmt::Result {
+fn write_rest(buf: &mut impl std::fmt::Write, mut x: u32, divisions: u32) -> std::fmt::Result {
+    use NoteValue::*;
     while x != 0 {
-        for bound in [(8, "whole"), (4, "half"), (2, "quarter"), (1, "eighth")] {
+        for bound in [(8, Whole), (4, Half), (2, Quarter), (1, Eighth)] {
             if x >= bound.0 {
-                write_muxml2_rest(buf, bound.1, bound.0 as u8)?;
+                write_muxml2_rest(buf, Rhythm::new(bound.1), divisions)?;
                 x -= bound.0;
             }
         }
@@ -68,6 +102,21 @@ fn write_rest(buf: &mut impl std::fmt::Write, mut x: u32) -> std::fmt::Result {
     Ok(())
 }
 
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// The time signature that fits a measure of this length, in [PER_QUARTER]ths of a quarter
+fn time_signature(duration: u32) -> (u32, usize) {
+    let fits = |denominator: u32| duration.is_multiple_of(PER_QUARTER * 4 / denominator);
+    let denominator = [8, 16].into_iter().find(|x| fits(*x)).unwrap_or(32);
+    (duration / (PER_QUARTER * 4 / denominator), denominator as usize)
+}
+
 #[derive(Default, Debug)]
 pub struct Slur {
     pub number: u16,
@@ -100,13 +149,69 @@ impl Slide {
         Slide { number, start: false }
     }
 }
+/// A slide into or out of a note that has no other note at its other end
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UnpairedSlide {
+    /// Into the note from below, `/7`
+    Scoop,
+    /// Into the note from above, `\7`
+    Plop,
+    /// Out of the note upwards, `7/`
+    Doit,
+    /// Out of the note downwards, `7\`
+    Falloff,
+}
+impl UnpairedSlide {
+    /// The name of its element in `<articulations>`
+    pub fn name(self) -> &'static str {
+        match self {
+            UnpairedSlide::Scoop => "scoop",
+            UnpairedSlide::Plop => "plop",
+            UnpairedSlide::Doit => "doit",
+            UnpairedSlide::Falloff => "falloff",
+        }
+    }
+}
+/// A bend of a note, or its release. There can be several on a note, like in `7b9r7`.
+#[derive(Debug, PartialEq)]
+pub struct Bend {
+    /// In semitones, relative to the end of the previous bend of the note
+    pub alter: i8,
+    pub pre: bool,
+    pub release: bool,
+}
 /// TODO: make this a bitstruct and see if that is faster
 /// TODO: try making this a SoA
 #[derive(Default, Debug)]
 pub struct NoteProperties {
     pub slurs: Vec<Slur>,
     pub slide: Option<Slide>,
+    pub slide_in: Option<UnpairedSlide>,
+    pub slide_out: Option<UnpairedSlide>,
     pub vibrato: Option<Vibrato>,
+    pub bends: Vec<Bend>,
+    pub tap: bool,
+    pub trill: bool,
+    pub tremolo: bool,
+    pub accent: bool,
+    pub staccato: bool,
+    pub lyric: Option<Syllable>,
+}
+impl NoteProperties {
+    /// Whether the note needs a `<notations>` element. Lyrics are written outside of it.
+    pub fn has_notations(&self) -> bool {
+        !self.slurs.is_empty()
+            || self.slide.is_some()
+            || self.slide_in.is_some()
+            || self.slide_out.is_some()
+            || self.vibrato.is_some()
+            || !self.bends.is_empty()
+            || self.tap
+            || self.trill
+            || self.tremolo
+            || self.accent
+            || self.staccato
+    }
 }
 #[derive(Debug)]
 pub enum Vibrato {
@@ -122,25 +227,56 @@ pub struct MuxmlGenerator {
     slur_cnt: u16,
     slide_cnt: u16,
     note_properties: HashMap<u32, NoteProperties, FxBuildHasher>,
+    /// The first and last tick with a note under each annotation, as indices into the tick stream
+    annotation_spans: Vec<(AnnotationKind, u32, u32)>,
+    /// The tick with a note that each chord symbol is attached to, and the index of the chord.
+    /// Sorted, and written in order with the help of `next_harmony`.
+    harmonies: Vec<(u32, usize)>,
+    next_harmony: usize,
+    /// `<divisions>` of the score, the number of duration units in a quarter
+    divisions: u32,
     r: BackendResult,
 }
 impl MuxmlGenerator {
+    /// The space needed for the part
     pub fn estimate_capacity(parsed: &ParserResult) -> usize {
-        // TODO: this is technically wrong, as .reserve reserves *additional* capacity.
-        // Should fix this, but that needs readjusting the *20 multiplier.
-        MUXML_INCOMPLETE_DOC_PRELUDE.len()
-            + MUXML2_DOCUMENT_END.len()
-            + parsed.tick_stream.len() * 20
+        MUXML2_PART_END.len() + MUXML2_DOCUMENT_END.len() + parsed.tick_stream.len() * 20
     }
     pub fn get_note_properties(&self, x: u32) -> Option<&NoteProperties> {
         self.note_properties.get(&x)
     }
-    /// Allocates heavily.
-    pub fn init(parsed: ParserResult, parse_time: Duration, settings: settings::Settings) -> Self {
+    /// Prepares writing the measures of a part at the end of `document`, which has everything
+    /// before them. Allocates heavily.
+    pub fn init(parsed: ParserResult, mut document: String, settings: settings::Settings) -> Self {
         let cap = Self::estimate_capacity(&parsed);
-        let mut document = String::from(MUXML_INCOMPLETE_DOC_PRELUDE);
         document.reserve(cap);
         debug!(capacity = cap, "reserved capacity");
+        let annotation_spans = Self::annotation_spans(&parsed);
+        let chord_ticks = parsed.chords.iter().map(|x| x.0);
+        let harmonies = Self::attach_to_notes(&parsed, chord_ticks);
+        let mut note_properties: HashMap<u32, NoteProperties, FxBuildHasher> = HashMap::default();
+        let lyric_ticks = parsed.lyrics.iter().map(|x| x.0);
+        for (tick_idx, lyric_idx) in Self::attach_to_notes(&parsed, lyric_ticks) {
+            let n = parsed.string_count;
+            let note_range = tick_idx as usize..tick_idx as usize + n;
+            let Some(offset) = parsed.tick_stream[note_range].iter().position(TabElement::is_note)
+            else {
+                continue;
+            };
+            // a note can only have a single syllable, the later ones are dropped
+            let properties = note_properties.entry(tick_idx + offset as u32).or_default();
+            if properties.lyric.is_none() {
+                properties.lyric = Some(parsed.lyrics[lyric_idx].1.clone());
+            }
+        }
+        // as coarse as possible, but fine enough for the shortest note. The default eighth can
+        // always be written.
+        let unit = parsed
+            .rhythm
+            .iter()
+            .flatten()
+            .fold(Rhythm::EIGHTH.duration(), |acc, x| gcd(acc, x.duration()));
+        let divisions = PER_QUARTER / unit;
 
         Self {
             parsed,
@@ -148,22 +284,66 @@ impl MuxmlGenerator {
             document,
             slur_cnt: 0,
             slide_cnt: 0,
-            note_properties: HashMap::default(),
-            r: BackendResult::new(vec![], None, Some(parse_time), None),
+            note_properties,
+            annotation_spans,
+            harmonies,
+            next_harmony: 0,
+            divisions,
+            r: BackendResult::new(vec![], vec![], None, None),
             measure_buf: vec![],
         }
     }
+    /// Annotations are attached to notes, so the ones without a note under them are dropped.
+    fn annotation_spans(parsed: &ParserResult) -> Vec<(AnnotationKind, u32, u32)> {
+        let n = parsed.string_count as u32;
+        let has_note = |tick: &u32| {
+            let start = (tick * n) as usize;
+            parsed.tick_stream[start..start + n as usize].iter().any(TabElement::is_note)
+        };
+        let spans = parsed.annotations.iter().filter_map(|annotation| {
+            let mut notes = annotation.ticks.clone().filter(has_note);
+            let first = notes.next()?;
+            let last = notes.next_back().unwrap_or(first);
+            Some((annotation.kind, first * n, last * n))
+        });
+        spans.collect()
+    }
+    /// Chord symbols and lyrics are attached to the first note at or after them in their measure,
+    /// or the last one before them if there is none. Takes the tick of each item, and returns the
+    /// stream index of the tick it is attached to along with the index of the item, sorted.
+    fn attach_to_notes(
+        parsed: &ParserResult, ticks: impl Iterator<Item = u32>,
+    ) -> Vec<(u32, usize)> {
+        let n = parsed.string_count as u32;
+        let is_note_tick = |tick: &u32| {
+            let start = (tick * n) as usize;
+            parsed.tick_stream[start..start + n as usize].iter().any(TabElement::is_note)
+        };
+        let mut attached: Vec<_> = (ticks.enumerate())
+            .filter_map(|(item_idx, tick)| {
+                let measure_idx =
+                    parsed.measures.partition_point(|x| *x.data_range.end() < tick * n);
+                let range = &parsed.measures.get(measure_idx)?.data_range;
+                let (first, last) = (range.start() / n, range.end() / n);
+                let note = (tick..=last).find(is_note_tick);
+                let note = note.or_else(|| (first..tick).rev().find(is_note_tick))?;
+                Some((note * n, item_idx))
+            })
+            .collect();
+        attached.sort();
+        attached
+    }
+    /// Writes the measures of the part, and returns the document.
     #[inline(always)]
     pub fn gen(mut self) -> (Option<String>, BackendResult) {
         let number_of_measures = self.parsed.measures.len();
         for measure_idx in 0..number_of_measures {
             if let Err(err) = self.process_measure(measure_idx) {
-                self.r.err = Some(err);
+                self.r.errors.push(err);
                 return (None, self.r);
             }
         }
 
-        self.document += MUXML2_DOCUMENT_END;
         debug!(cap = self.document.capacity(), "document capacity on finish",);
         (Some(self.document), self.r)
     }
@@ -194,12 +374,14 @@ impl MuxmlGenerator {
     pub fn stop_vibrato(&mut self, idx: u32) {
         self.note_properties.entry(idx).or_default().vibrato = Some(Vibrato::Stop);
     }
+    /// Slurs the notes around a hammer-on, pull-off or release.
     pub fn process_bend_like(&mut self, stream_idx: usize) -> Result<(), BackendError> {
         // TODO: eventually mark hammerOns and pulls
-        let last_idx = stream_idx.saturating_sub(6);
+        let n = self.parsed.string_count;
+        let last_idx = stream_idx.saturating_sub(n);
         trace!(stream_idx, "muxml2: have bend at tick {stream_idx}. last element on this string is (@{last_idx}): {:?}", self.parsed.tick_stream[last_idx]);
         self.start_slur(last_idx as u32);
-        let next_idx = stream_idx + 6;
+        let next_idx = stream_idx + n;
 
         match &self.parsed.tick_stream.get(next_idx) {
             None => {
@@ -210,7 +392,7 @@ impl MuxmlGenerator {
                     return Err(BackendError::bend_on_invalid(line, char));
                 };
 
-                self.parsed.tick_stream.extend([const { TabElement::Rest }; 6]);
+                self.parsed.tick_stream.extend(std::iter::repeat_n(TabElement::Rest, n));
                 self.parsed.tick_stream[next_idx] = TabElement::Fret(x + 1);
                 self.stop_slur(next_idx as u32);
             }
@@ -232,13 +414,93 @@ impl MuxmlGenerator {
         trace!(start_idx = last_idx, end_idx = next_idx, "added bend");
         Ok(())
     }
+    /// A slide between two notes is a `<slide>`. Without a note before it, it slides into the next
+    /// note, and without a note after it, out of the previous one.
+    pub fn process_slide(&mut self, stream_idx: usize, down: bool) {
+        let n = self.parsed.string_count;
+        let stream = &self.parsed.tick_stream;
+        let note_at =
+            |idx: Option<usize>| idx.filter(|x| stream.get(*x).is_some_and(TabElement::is_note));
+        let (last_idx, next_idx) =
+            (note_at(stream_idx.checked_sub(n)), note_at(Some(stream_idx + n)));
+        trace!(stream_idx, ?last_idx, ?next_idx, down, "have Slide");
+        match (last_idx, next_idx) {
+            (Some(last_idx), Some(next_idx)) => {
+                self.start_slide(last_idx as u32);
+                self.stop_slide(next_idx as u32);
+            }
+            (None, Some(next_idx)) => {
+                let properties = self.note_properties.entry(next_idx as u32).or_default();
+                properties.slide_in =
+                    Some(if down { UnpairedSlide::Plop } else { UnpairedSlide::Scoop });
+            }
+            (Some(last_idx), None) => {
+                let properties = self.note_properties.entry(last_idx as u32).or_default();
+                properties.slide_out =
+                    Some(if down { UnpairedSlide::Falloff } else { UnpairedSlide::Doit });
+            }
+            (None, None) => trace!("slide without notes around it, ignoring"),
+        }
+    }
+    /// Whether the release at `stream_idx` comes after a bend on its string, with only rests
+    /// between them
+    fn releases_bend(&self, stream_idx: usize) -> bool {
+        let n = self.parsed.string_count;
+        let mut before = (stream_idx % n..stream_idx).step_by(n).rev();
+        let prev = before.find(|x| self.parsed.tick_stream[*x] != TabElement::Rest);
+        let prev = prev.map(|x| &self.parsed.tick_stream[x]);
+        matches!(prev, Some(TabElement::Bend { .. } | TabElement::Release { .. }))
+    }
+    /// Adds a bend or a release to the note it belongs to, which is the last one on its string
+    /// before any other bends and releases.
+    /// A bend has to come right after its note or another bend, but there can be rests before a
+    /// release, like in `7b9--r7`.
+    pub fn process_bend(&mut self, stream_idx: usize) -> Result<(), BackendError> {
+        let n = self.parsed.string_count;
+        let stream = &self.parsed.tick_stream;
+        let mut note_idx = stream_idx;
+        let mut skip_rests = matches!(stream[stream_idx], TabElement::Release { .. });
+        let fret = loop {
+            let Some(prev) = note_idx.checked_sub(n) else { break None };
+            note_idx = prev;
+            match &stream[note_idx] {
+                TabElement::Rest if skip_rests => {}
+                TabElement::Bend { .. } => skip_rests = false,
+                TabElement::Release { .. } => skip_rests = true,
+                x => break x.sounding_offset(),
+            }
+        };
+        let Some(fret) = fret else {
+            let (line, char) =
+                source_location_from_stream(&self.parsed.as_ref(), stream_idx as u32);
+            return Err(BackendError::bend_on_invalid(line, char));
+        };
+        let elem = &stream[stream_idx];
+        let bends = &mut self.note_properties.entry(note_idx as u32).or_default().bends;
+        let current = bends.iter().map(|x| x.alter as i32).sum::<i32>().clamp(0, u8::MAX as i32);
+        let Some(target) = elem.bend_target(fret, current as u8) else {
+            let (line, char) =
+                source_location_from_stream(&self.parsed.as_ref(), stream_idx as u32);
+            return Err(BackendError::invalid_bend_target(line, char));
+        };
+        let bend = Bend {
+            alter: (target as i32 - current).clamp(i8::MIN as i32, i8::MAX as i32) as i8,
+            pre: matches!(elem, TabElement::Bend { pre: true, .. }),
+            release: matches!(elem, TabElement::Release { .. }),
+        };
+        trace!(note_idx, ?bend, "added bend");
+        bends.push(bend);
+        Ok(())
+    }
     pub fn process_measure(&mut self, measure_idx: usize) -> Result<(), BackendError> {
         let meas = span!(Level::TRACE, "Muxml2: processing measure", measure_idx);
         let _meas = meas.enter();
 
-        let data_range = &self.parsed.measures[measure_idx].data_range;
-        let ticks_in_measure = rlen(data_range) / 6;
-        debug_assert!(rlen(data_range).is_multiple_of(6));
+        let n = self.parsed.string_count;
+        let data_range = self.parsed.measures[measure_idx].data_range.clone();
+        let has_rhythm = self.parsed.measures[measure_idx].has_rhythm;
+        let ticks_in_measure = rlen(&data_range) / n as u32;
+        debug_assert!(rlen(&data_range).is_multiple_of(n as u32));
 
         // Length of actual content in measure. `remove_space_between_notes` will reduce this for example
         let mut measure_content_len = ticks_in_measure;
@@ -254,39 +516,59 @@ impl MuxmlGenerator {
             //    "current elem: {elem:?}, note_count: {note_count}, proc_cnt = {stream_proc_cnt}"
             //);
             match elem {
-                TabElement::Fret(..) | TabElement::DeadNote => note_count += 1,
+                TabElement::Fret(..)
+                | TabElement::NaturalHarmonic(..)
+                | TabElement::ArtificialHarmonic { .. }
+                | TabElement::GhostNote(..)
+                | TabElement::DeadNote => note_count += 1,
+                TabElement::Tap => {
+                    self.note_properties.entry((stream_idx + n) as u32).or_default().tap = true
+                }
+                TabElement::Trill
+                | TabElement::Tremolo { .. }
+                | TabElement::Accent
+                | TabElement::Staccato => {
+                    // repeated marks like `5===` only apply once, to the note before them
+                    let last_idx = stream_idx.saturating_sub(n);
+                    if self.parsed.tick_stream[last_idx].sounding_offset().is_some() {
+                        let properties = self.note_properties.entry(last_idx as u32).or_default();
+                        match elem {
+                            TabElement::Trill => properties.trill = true,
+                            TabElement::Accent => properties.accent = true,
+                            TabElement::Staccato => properties.staccato = true,
+                            _ => properties.tremolo = true,
+                        }
+                    }
+                }
                 TabElement::Rest => {}
                 TabElement::Vibrato => {
-                    self.start_vibrato(stream_idx.saturating_sub(6) as u32);
-                    let next_idx = stream_idx + 6;
+                    self.start_vibrato(stream_idx.saturating_sub(n) as u32);
+                    let next_idx = stream_idx + n;
                     if next_idx >= self.parsed.tick_stream.len() {
-                        self.parsed.tick_stream.extend([const { TabElement::Rest }; 6]);
+                        self.parsed.tick_stream.extend(std::iter::repeat_n(TabElement::Rest, n));
                     }
                     self.stop_vibrato(next_idx as u32);
                 }
-                TabElement::Bend
-                | TabElement::HammerOn
-                | TabElement::Pull
-                | TabElement::Release => self.process_bend_like(stream_idx)?,
-                TabElement::Slide => {
-                    let last_idx = stream_idx.saturating_sub(6);
-                    trace!(
-                        "have Slide. last element on this string is: {:?}",
-                        self.parsed.tick_stream[last_idx]
-                    );
-                    self.start_slide(last_idx as u32);
-                    let next_idx = stream_idx + 6;
-                    if next_idx < self.parsed.tick_stream.len() {
-                        self.stop_slide(next_idx as u32);
-                    }
-                    trace!(start_idx = stream_idx, end_idx = next_idx, "added slide")
+                TabElement::Bend { .. } => self.process_bend(stream_idx)?,
+                // a release without a bend before it is played like a pull-off
+                TabElement::Release { .. } if self.releases_bend(stream_idx) => {
+                    self.process_bend(stream_idx)?
+                }
+                TabElement::HammerOn | TabElement::Pull | TabElement::Release { .. } => {
+                    self.process_bend_like(stream_idx)?
                 }
+                TabElement::Slide { down } => self.process_slide(stream_idx, *down),
             }
             stream_idx += 1;
 
-            if stream_proc_cnt == 5 {
-                if note_count > 0 {
-                    self.measure_buf.push(Muxml2TabElement::CopyTick(stream_idx as u32 - 6));
+            if stream_proc_cnt == n - 1 {
+                let tick_idx = stream_idx - n;
+                if has_rhythm && self.parsed.rhythm[tick_idx / n].is_none() {
+                    trace!("spacing between notes, skipping");
+                } else if note_count > 0 {
+                    self.measure_buf.push(Muxml2TabElement::CopyTick(tick_idx as u32));
+                } else if has_rhythm {
+                    self.measure_buf.push(Muxml2TabElement::TimedRest(tick_idx as u32));
                 } else {
                     self.measure_buf.push(Muxml2TabElement::Rest(1));
                 }
@@ -296,30 +578,78 @@ impl MuxmlGenerator {
                 stream_proc_cnt += 1;
             }
         }
-        if self.settings.remove_rest_between_notes {
-            remove_rest_between_notes(&mut self.measure_buf, &mut measure_content_len);
-        }
-        merge_rests_in_measure(&mut self.measure_buf);
-        if self.settings.trim_measure {
-            trim_measure(&mut self.measure_buf, &mut measure_content_len, Direction::Forward);
-            trim_measure(&mut self.measure_buf, &mut measure_content_len, Direction::Backward);
-        }
+        // the rests of a rhythm line are written on purpose, so they are kept as they are
+        let measure_duration = if has_rhythm {
+            let ticks = data_range.clone().step_by(n).map(|x| self.parsed.rhythm[x as usize / n]);
+            ticks.flatten().map(|x| x.duration()).sum()
+        } else {
+            if self.settings.remove_rest_between_notes {
+                remove_rest_between_notes(&mut self.measure_buf, &mut measure_content_len);
+            }
+            merge_rests_in_measure(&mut self.measure_buf);
+            if self.settings.trim_measure {
+                trim_measure(&mut self.measure_buf, &mut measure_content_len, Direction::Forward);
+                trim_measure(&mut self.measure_buf, &mut measure_content_len, Direction::Backward);
+            }
+            measure_content_len * Rhythm::EIGHTH.duration()
+        };
         // Try to simplify e.g 8/8 to 4/4
-        let (mut measure_enumerator, mut measure_denominator) = (measure_content_len, 8);
-        if self.settings.simplify_time_signature && measure_content_len.is_multiple_of(2) {
+        let (mut measure_enumerator, mut measure_denominator) = time_signature(measure_duration);
+        if self.settings.simplify_time_signature && measure_enumerator.is_multiple_of(2) {
             measure_enumerator /= 2;
             measure_denominator /= 2;
         }
+        // a time signature in the header wins, unless the measure doesn't fit in it, as then the
+        // notes would add up to another one
+        if let Some((beats, beat_type)) = self.parsed.metadata.time {
+            let header_duration = beats as u32 * PER_QUARTER * 4 / beat_type as u32;
+            if measure_duration == 0 || measure_duration == header_duration {
+                (measure_enumerator, measure_denominator) = (beats as u32, beat_type as usize);
+            } else {
+                let (line, char) =
+                    source_location_from_stream(&self.parsed.as_ref(), *data_range.start());
+                let location = ErrorLocation::LineAndChar(line, char);
+                let kind = DiagnosticKind::MeasureDoesntFitTime { beats, beat_type };
+                self.r.diagnostics.push(Diagnostic::warn(location, kind));
+            }
+        }
         write_muxml2_measure_prelude(
             &mut self.document,
             measure_idx,
             measure_enumerator as usize,
             measure_denominator,
+            self.divisions,
+            self.parsed.metadata.capo.unwrap_or(0),
         )
         .unwrap();
+        if let Some(tempo) = self.parsed.metadata.tempo.filter(|_| measure_idx == 0) {
+            write_muxml2_tempo(&mut self.document, tempo)?;
+        }
+        for (_, name) in self.parsed.sections.iter().filter(|x| x.0 as usize == measure_idx) {
+            write_muxml2_rehearsal(&mut self.document, name)?;
+        }
+        let measure = &self.parsed.measures[measure_idx];
+        let (repeat_start, repeat_end, ending) =
+            (measure.repeat_start, measure.repeat_end, measure.ending);
+        if repeat_start || ending.is_some() {
+            write_muxml2_left_barline(&mut self.document, repeat_start, ending)?;
+        }
         for i in 0..self.measure_buf.len() {
             self.write_tab_element(i)?;
         }
+        // the text after the end of the lines, once even if several strings have it
+        let mut texts: Vec<&str> = vec![];
+        for x in self.parsed.trailing.iter().filter(|x| x.measure as usize == measure_idx) {
+            if let Trailing::Text(text) = &x.trailing {
+                if !texts.contains(&text.as_str()) {
+                    write_muxml2_words(&mut self.document, text)?;
+                    texts.push(text);
+                }
+            }
+        }
+        if repeat_end.is_some() || ending.is_some() {
+            write_muxml2_right_barline(&mut self.document, repeat_end, ending)?;
+        }
         self.document.push_str("</measure>");
         Ok(())
     }
@@ -328,36 +658,75 @@ impl MuxmlGenerator {
     pub fn write_tab_element(&mut self, elem_idx: usize) -> std::fmt::Result {
         let elem = &self.measure_buf[elem_idx];
         match elem {
-            Muxml2TabElement::Rest(x) => write_rest(&mut self.document, *x),
+            Muxml2TabElement::Rest(x) => write_rest(&mut self.document, *x, self.divisions),
+            Muxml2TabElement::TimedRest(tick_idx) => {
+                let n = self.parsed.string_count;
+                let rhythm = self.parsed.rhythm[*tick_idx as usize / n].unwrap_or(Rhythm::EIGHTH);
+                write_muxml2_rest(&mut self.document, rhythm, self.divisions)
+            }
             Muxml2TabElement::CopyTick(tick_idx) => {
+                let n = self.parsed.string_count;
                 let idu = *tick_idx as usize;
-                let note_range = idu..=(idu + 5);
+                let note_range = idu..=(idu + n - 1);
                 let notes_iter = self.parsed.tick_stream[note_range.clone()]
                     .iter()
                     .enumerate()
-                    .filter(|x| !matches!(x.1, TabElement::Rest))
+                    .filter(|x| x.1.is_note())
                     .map(|(x, y)| (x + idu, y));
                 // at least two notes here
                 let tick_chord = notes_iter.clone().take(2).count() == 2;
                 trace!(?note_range, chord = tick_chord, "for CopyTick({tick_idx})");
-                // TODO: use dynamic base notes - we parse it but we don't use it
+                let rhythm = self.parsed.rhythm[idu / n].unwrap_or(Rhythm::EIGHTH);
+                while let Some((harmony_tick, chord_idx)) = self.harmonies.get(self.next_harmony) {
+                    if harmony_tick > tick_idx {
+                        break;
+                    }
+                    if harmony_tick == tick_idx {
+                        write_muxml2_harmony(
+                            &mut self.document,
+                            &self.parsed.chords[*chord_idx].1,
+                        )?;
+                    }
+                    self.next_harmony += 1;
+                }
+                for (i, (kind, first, _)) in self.annotation_spans.iter().enumerate() {
+                    if first == tick_idx {
+                        write_muxml2_dashes_start(&mut self.document, kind.label(), i % 16 + 1)?;
+                    }
+                }
                 for (elem_idx, elem) in notes_iter {
-                    let Some((dead, fret)) = (match elem {
-                        TabElement::DeadNote => Some((true, 0)),
-                        TabElement::Fret(x) => Some((false, *x)),
-                        _ => None,
-                    }) else {
-                        continue;
+                    let kind = match elem {
+                        TabElement::DeadNote => NoteKind::Dead,
+                        TabElement::Fret(..) => NoteKind::Normal,
+                        TabElement::GhostNote(..) => NoteKind::Ghost,
+                        TabElement::NaturalHarmonic(..) => NoteKind::NaturalHarmonic,
+                        TabElement::ArtificialHarmonic { .. } => NoteKind::ArtificialHarmonic,
+                        _ => continue,
                     };
-                    let string_name = self.parsed.base_notes[elem_idx % 6];
-                    let note = get_fretboard_note2(string_name, fret).unwrap_or_else(|| {
-                        panic!("Don't know base note for string name {string_name}",)
-                    });
+                    // harmonics are written at their sounding pitch
+                    let fret = elem.sounding_offset().unwrap_or(0);
+                    let note =
+                        get_fretboard_note2(self.parsed.open_string_pitch(elem_idx % n), fret);
                     let (step, octave, sharp) = note.step_octave_sharp();
                     let properties = self.note_properties.get(&(elem_idx as u32));
                     let doc = &mut self.document;
                     let need_chord = tick_chord && elem_idx > 0;
-                    write_muxml2_note(doc, step, octave, sharp, need_chord, dead, properties)?;
+                    write_muxml2_note(
+                        doc,
+                        step,
+                        octave,
+                        sharp,
+                        need_chord,
+                        kind,
+                        rhythm,
+                        self.divisions,
+                        properties,
+                    )?;
+                }
+                for (i, (_, _, last)) in self.annotation_spans.iter().enumerate() {
+                    if last == tick_idx {
+                        write_muxml2_dashes_stop(&mut self.document, i % 16 + 1)?;
+                    }
                 }
 
                 Ok(())
@@ -379,7 +748,9 @@ fn merge_rests_in_measure(measure: &mut [Muxml2TabElement]) {
                 }
                 measure[original_i] = Muxml2TabElement::Rest((i - original_i) as u32);
             }
-            Muxml2TabElement::CopyTick(..) | Muxml2TabElement::Invalid => continue,
+            Muxml2TabElement::CopyTick(..)
+            | Muxml2TabElement::TimedRest(..)
+            | Muxml2TabElement::Invalid => continue,
         }
     }
 }
@@ -425,7 +796,7 @@ fn trim_measure(measure: &mut [Muxml2TabElement], content_len: &mut u32, directi
                 measure[i] = Muxml2TabElement::Invalid;
                 break;
             }
-            Muxml2TabElement::CopyTick(_) => break,
+            Muxml2TabElement::CopyTick(_) | Muxml2TabElement::TimedRest(_) => break,
             Muxml2TabElement::Invalid => {
                 if i == last {
                     break;
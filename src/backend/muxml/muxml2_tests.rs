@@ -40,7 +40,20 @@ E|----|
         trim_measure: true,
         simplify_time_signature: true,
     };
-    MuxmlBackend::process(&i1.into(), &mut out, settings);
+    MuxmlBackend::process(&i1.into(), &mut out, settings.clone());
+    insta::assert_snapshot!(String::from_utf8_lossy(&out));
+
+    // targets, pre-bends and releases
+    let i2 = r#"
+e|------|
+B|7b9r7-|
+G|7pb(9)|
+D|------|
+A|------|
+E|------|
+    "#;
+    let mut out = vec![];
+    MuxmlBackend::process(&i2.into(), &mut out, settings);
     insta::assert_snapshot!(String::from_utf8_lossy(&out));
     Ok(())
 }
@@ -69,8 +82,336 @@ E|-----9-|-----9-|"#;
         simplify_time_signature: true,
     };
     let res = MuxmlBackend::process(&example_score.into(), &mut Vec::new(), settings);
-    let e = res.err.unwrap();
+    let e = res.errors.into_iter().next().unwrap();
     use crate::backend::errors::error_location::ErrorLocation;
     assert_eq!(e.main_location, ErrorLocation::LineAndChar(3, 25));
     assert!(matches!(e.kind, BackendErrorKind::BendOnInvalid));
 }
+#[test]
+fn test_invalid_bend_targets() {
+    // `12b12` doesn't bend anywhere, and isn't read as six whole tones
+    let score = "e|12b12-|\nB|------|\nG|------|\n";
+    let settings = Settings {
+        remove_rest_between_notes: false,
+        trim_measure: false,
+        simplify_time_signature: false,
+    };
+    let res = MuxmlBackend::process(&score.into(), &mut Vec::new(), settings);
+    let e = res.errors.into_iter().next().unwrap();
+    use crate::backend::errors::error_location::ErrorLocation;
+    assert_eq!(e.main_location, ErrorLocation::LineAndChar(0, 4));
+    assert!(matches!(e.kind, BackendErrorKind::InvalidBendTarget));
+}
+
+#[test]
+fn test_muxml_metadata() {
+    let i1 = r#"
+title: Rock & Roll
+artist: Somebody
+tempo: 120
+capo: 3
+time: 2/4
+
+e|----|
+B|----|
+G|----|
+D|--0-|
+A|-3--|
+E|----|
+    "#;
+    let mut out = vec![];
+    let settings = Settings {
+        remove_rest_between_notes: false,
+        trim_measure: false,
+        simplify_time_signature: false,
+    };
+    MuxmlBackend::process(&i1.into(), &mut out, settings);
+    insta::assert_snapshot!(String::from_utf8_lossy(&out));
+}
+
+#[test]
+fn test_muxml_time_mismatch() {
+    use crate::backend::errors::{diagnostic_kind::DiagnosticKind, error_location::ErrorLocation};
+    let i1 = r#"time: 3/4
+e|0-----|0---|
+B|------|----|
+G|------|----|
+D|------|----|
+A|------|----|
+E|------|----|
+"#;
+    let mut out = vec![];
+    let settings = Settings {
+        remove_rest_between_notes: false,
+        trim_measure: false,
+        simplify_time_signature: true,
+    };
+    let r = MuxmlBackend::process(&i1.into(), &mut out, settings);
+    // the measure that is too short gets the signature it adds up to, with a warning
+    let out = String::from_utf8_lossy(&out);
+    let beats: Vec<_> = out.match_indices("<beats>").map(|(i, _)| &out[i + 7..i + 8]).collect();
+    assert_eq!(beats, ["3", "2"]);
+    assert_eq!(r.diagnostics.len(), 1);
+    let diagnostic = &r.diagnostics[0];
+    assert!(matches!(
+        diagnostic.kind,
+        DiagnosticKind::MeasureDoesntFitTime { beats: 3, beat_type: 4 }
+    ));
+    assert_eq!(diagnostic.location, ErrorLocation::LineAndChar(1, 9));
+}
+
+#[test]
+fn test_muxml_repeats() {
+    let i1 = r#"
+      1.  2.
+e|:--|-:|--|
+B|:--|-:|--|
+G|:2-|2:|-2|
+D|:--|-:|--|
+A|:--|-:|--|
+E|:--|-:|--|
+    "#;
+    let mut out = vec![];
+    let settings = Settings {
+        remove_rest_between_notes: false,
+        trim_measure: false,
+        simplify_time_signature: false,
+    };
+    MuxmlBackend::process(&i1.into(), &mut out, settings);
+    insta::assert_snapshot!(String::from_utf8_lossy(&out));
+}
+#[test]
+fn test_muxml_harmonics() {
+    let i1 = r#"
+e|<12>|[7]|
+B|----|---|
+G|----|---|
+D|----|---|
+A|----|---|
+E|----|---|
+    "#;
+    let mut out = vec![];
+    let settings = Settings {
+        remove_rest_between_notes: false,
+        trim_measure: false,
+        simplify_time_signature: false,
+    };
+    MuxmlBackend::process(&i1.into(), &mut out, settings);
+    insta::assert_snapshot!(String::from_utf8_lossy(&out));
+}
+#[test]
+fn test_muxml_annotations() {
+    let i1 = r#"
+  PM---   l.r.---
+e|0-0-|--|0-0|
+B|----|--|---|
+G|----|--|---|
+D|----|--|---|
+A|----|--|---|
+E|----|--|---|
+    "#;
+    let mut out = vec![];
+    let settings = Settings {
+        remove_rest_between_notes: false,
+        trim_measure: false,
+        simplify_time_signature: false,
+    };
+    MuxmlBackend::process(&i1.into(), &mut out, settings);
+    insta::assert_snapshot!(String::from_utf8_lossy(&out));
+}
+#[test]
+fn test_muxml_techniques() {
+    let i1 = r#"
+e|t12|------|
+B|---|(5)---|
+G|---|------|
+D|---|---7tr|
+A|---|------|
+E|9=-|------|
+    "#;
+    let mut out = vec![];
+    let settings = Settings {
+        remove_rest_between_notes: false,
+        trim_measure: false,
+        simplify_time_signature: false,
+    };
+    MuxmlBackend::process(&i1.into(), &mut out, settings);
+    insta::assert_snapshot!(String::from_utf8_lossy(&out));
+}
+#[test]
+fn test_muxml_slides() {
+    let i1 = r#"
+e|5/7-|/5------|
+B|----|--7\----|
+G|----|--------|
+D|----|----\9--|
+A|----|------9/|
+E|----|--------|
+    "#;
+    let mut out = vec![];
+    let settings = Settings {
+        remove_rest_between_notes: false,
+        trim_measure: false,
+        simplify_time_signature: false,
+    };
+    MuxmlBackend::process(&i1.into(), &mut out, settings);
+    insta::assert_snapshot!(String::from_utf8_lossy(&out));
+}
+#[test]
+fn test_muxml_articulations() {
+    use crate::parser::{dialect::Dialect, settings::ParserSettings};
+    let i1 = r#"
+e|5*-7.-|
+B|------|
+G|------|
+D|------|
+A|------|
+E|------|
+    "#;
+    let mut out = vec![];
+    let settings = Settings {
+        remove_rest_between_notes: false,
+        trim_measure: false,
+        simplify_time_signature: false,
+    };
+    let parser_settings = ParserSettings { dialect: Dialect::classic(), ..Default::default() };
+    MuxmlBackend::process_with(&i1.into(), parser_settings, &mut out, settings);
+    insta::assert_snapshot!(String::from_utf8_lossy(&out));
+}
+#[test]
+fn test_muxml_rhythm() {
+    let i1 = r#"
+  Q   E.S E3E3E3|H   Q  Q
+e|0---2-3-5-5-5-|7---0--|
+B|--------------|-------|
+G|--------------|-------|
+D|--------------|-------|
+A|--------------|-------|
+E|--------------|-------|
+    "#;
+    let mut out = vec![];
+    let settings = Settings {
+        remove_rest_between_notes: true,
+        trim_measure: true,
+        simplify_time_signature: true,
+    };
+    MuxmlBackend::process(&i1.into(), &mut out, settings);
+    insta::assert_snapshot!(String::from_utf8_lossy(&out));
+}
+#[test]
+fn test_muxml_chords() {
+    let i1 = r#"
+  Am  F#m7|Bb/D
+e|0---2---|1---|
+B|--------|----|
+G|--------|----|
+D|--------|----|
+A|--------|----|
+E|--------|----|
+    "#;
+    let mut out = vec![];
+    let settings = Settings {
+        remove_rest_between_notes: false,
+        trim_measure: false,
+        simplify_time_signature: false,
+    };
+    MuxmlBackend::process(&i1.into(), &mut out, settings);
+    insta::assert_snapshot!(String::from_utf8_lossy(&out));
+}
+
+#[test]
+fn test_muxml_lyrics() {
+    let i1 = r#"
+e|0---2---|1---|
+B|--------|----|
+G|--------|----|
+D|--------|----|
+A|--------|----|
+E|--------|----|
+  Hel-lo   world
+    "#;
+    let mut out = vec![];
+    let settings = Settings {
+        remove_rest_between_notes: false,
+        trim_measure: false,
+        simplify_time_signature: false,
+    };
+    MuxmlBackend::process(&i1.into(), &mut out, settings);
+    insta::assert_snapshot!(String::from_utf8_lossy(&out));
+}
+
+#[test]
+fn test_muxml_sections() {
+    let i1 = r#"
+[Verse]
+e|0---|2---|
+B|----|----|
+G|----|----|
+D|----|----|
+A|----|----|
+E|----|----|
+
+Chorus:
+e|1---|
+B|----|
+G|----|
+D|----|
+A|----|
+E|----|
+    "#;
+    let mut out = vec![];
+    let settings = Settings {
+        remove_rest_between_notes: false,
+        trim_measure: false,
+        simplify_time_signature: false,
+    };
+    MuxmlBackend::process(&i1.into(), &mut out, settings);
+    insta::assert_snapshot!(String::from_utf8_lossy(&out));
+}
+
+#[test]
+fn test_muxml_trailing() {
+    let i1 = r#"
+e|0---|2---|  x2
+B|----|----|
+G|----|----|
+D|----|----|
+A|----|----|
+E|----|3---|  <- let ring
+    "#;
+    let mut out = vec![];
+    let settings = Settings {
+        remove_rest_between_notes: false,
+        trim_measure: false,
+        simplify_time_signature: false,
+    };
+    MuxmlBackend::process(&i1.into(), &mut out, settings);
+    insta::assert_snapshot!(String::from_utf8_lossy(&out));
+}
+
+#[test]
+fn test_muxml_instruments() {
+    let i1 = r#"
+Gtr I
+e|0---|
+B|----|
+G|----|
+D|----|
+A|----|
+E|----|
+
+Bass
+G|----|
+D|----|
+A|--0-|
+E|----|
+    "#;
+    let mut out = vec![];
+    let settings = Settings {
+        remove_rest_between_notes: false,
+        trim_measure: false,
+        simplify_time_signature: false,
+    };
+    MuxmlBackend::process(&i1.into(), &mut out, settings);
+    insta::assert_snapshot!(String::from_utf8_lossy(&out));
+}
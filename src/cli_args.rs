@@ -1,9 +1,13 @@
 use std::fmt::Display;
 
+use anyhow::Context;
 use clap::{Parser, Subcommand};
-use scoreman::backend::{
-    fixup::{FixupBackendSettings, FixupDumpOptions},
-    muxml, BackendSelector,
+use scoreman::{
+    backend::{
+        fixup::{FixupBackendSettings, FixupDumpOptions},
+        muxml, BackendSelector,
+    },
+    parser::{dialect::Dialect, settings::ParserSettings},
 };
 
 #[derive(Parser)]
@@ -18,6 +22,46 @@ pub struct Cli {
     /// Don't print debug timings
     #[arg(short = 'q', long)]
     pub quiet: bool,
+    /// The number of strings of the instrument. Detected from the first part if not given.
+    #[arg(long, global = true, value_parser = clap::value_parser!(u8).range(1..))]
+    pub strings: Option<u8>,
+    /// Keep going after an error and report every error of the score
+    #[arg(long, global = true)]
+    pub recover: bool,
+    /// Also accept parts without string names or with unusual barlines, like `|---3---|` or
+    /// `e:---|`, and report every guess that takes
+    #[arg(long, global = true)]
+    pub lenient: bool,
+    /// The symbols the tab uses for techniques: one of the built-in dialects (standard,
+    /// ultimate-guitar, classic) or the path of a dialect file
+    #[arg(long, global = true, default_value = "standard")]
+    pub dialect: String,
+    /// Record where every element of the score is in the source, which makes the locations of
+    /// errors exact but takes memory for every element
+    #[arg(long, global = true)]
+    pub spans: bool,
+}
+
+impl Cli {
+    pub fn parser_settings(&self) -> anyhow::Result<ParserSettings> {
+        Ok(ParserSettings {
+            string_count: self.strings.map(usize::from),
+            recover: self.recover,
+            lenient: self.lenient,
+            spans: self.spans,
+            dialect: self.dialect()?,
+        })
+    }
+    /// A built-in dialect, or else one read from the file at that path
+    fn dialect(&self) -> anyhow::Result<Dialect> {
+        if Dialect::BUILT_IN.contains(&self.dialect.as_str()) {
+            return Ok(Dialect::built_in(&self.dialect)?);
+        }
+        let path = &self.dialect;
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read dialect file {path}"))?;
+        Dialect::parse(&text).with_context(|| format!("Invalid dialect file {path}"))
+    }
 }
 
 #[derive(Subcommand)]
@@ -43,7 +87,14 @@ pub enum Commands {
         output_path: String,
     },
     /// The simplest backend, used mainly for playback in interactive applications. Produces a .smf file.
-    Midi { input_path: String, output_path: String },
+    Midi {
+        /// Parse and convert the input while reading it, which keeps memory use low for very large
+        /// inputs. Repeats can't span parts in this mode.
+        #[arg(short = 's', long)]
+        stream: bool,
+        input_path: String,
+        output_path: String,
+    },
 
     /// Tries to fix errors in the score, until it can be parsed.
     Fixup {
@@ -56,6 +107,11 @@ pub enum Commands {
 }
 
 impl Commands {
+    /// Whether the input should be read as a stream instead of all at once
+    pub fn is_streaming(&self) -> bool {
+        matches!(self, Commands::Midi { stream: true, .. })
+    }
+
     pub fn input_path(&self) -> &str {
         match self {
             Commands::Muxml { input_path, .. } | Commands::Midi { input_path, .. } => input_path,
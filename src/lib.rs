@@ -41,6 +41,9 @@ impl BufLines {
     /// assert_eq!(lines.get_line(1).len(), lines.line_len(1));
     /// assert_eq!(lines.get_line(2), "World");
     /// ```
+    ///
+    /// Lines are split on `\n` only. Input that comes from files should be read with
+    /// [parser::normalize::SourceMap::normalize] instead.
     pub fn from_string(buf: String) -> Self {
         let mut line_ends = Vec::with_capacity(buf.len() / 32);
         line_ends.extend(memchr_iter(b'\n', buf.as_bytes()));
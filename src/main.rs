@@ -1,7 +1,7 @@
 use std::{
     fmt::Write,
     fs::{File, OpenOptions},
-    io::{BufWriter, Read, StdoutLock},
+    io::{BufRead, BufReader, BufWriter, Read, StdoutLock},
     sync::Arc,
 };
 
@@ -9,28 +9,42 @@ use anyhow::{Context, Ok};
 use clap::Parser;
 use entrace_core::remote::IETStorage;
 use scoreman::{
-    backend::errors::{
-        backend_error::BackendError, diagnostic::Diagnostic, error_location::ErrorLocation,
-        extend_error_range,
+    backend::{
+        errors::{
+            backend_error::BackendError, diagnostic::Diagnostic, error_location::ErrorLocation,
+            extend_error_range,
+        },
+        midi::MidiBackend,
     },
-    digit_cnt_usize, BufLines, ParseLines,
+    digit_cnt_usize,
+    parser::normalize::SourceMap,
+    BufLines, ParseLines,
 };
 use yansi::{Paint, Painted};
 mod cli_args;
 use crate::cli_args::Cli;
 
-fn get_file(path: &str) -> anyhow::Result<BufLines> {
-    let mut buf = String::new();
+fn open_file(path: &str) -> anyhow::Result<Box<dyn BufRead>> {
+    if path == "-" {
+        return Ok(Box::new(std::io::stdin().lock()));
+    }
+    let file = File::open(path).with_context(|| format!("Failed to open file {path}"))?;
+    Ok(Box::new(BufReader::new(file)))
+}
+
+/// Reads and normalises the input, see [SourceMap::normalize].
+fn get_file(path: &str) -> anyhow::Result<(BufLines, SourceMap)> {
+    let mut buf = vec![];
     if path == "-" {
         let mut f = std::io::stdin();
-        f.read_to_string(&mut buf)?;
+        f.read_to_end(&mut buf)?;
     } else {
         let mut file = File::open(path).with_context(|| format!("Failed to open file {path}"))?;
         let size = file.metadata().map(|m| m.len()).unwrap_or(0);
         buf.reserve_exact(size as usize);
-        file.read_to_string(&mut buf)?;
+        file.read_to_end(&mut buf)?;
     }
-    Ok(BufLines::from_string(buf))
+    Ok(SourceMap::normalize(&buf))
 }
 
 enum OutputType {
@@ -77,7 +91,7 @@ fn main() -> anyhow::Result<()> {
     let trace_storage = setup_tracing();
     let cli = Cli::parse();
     let input_path = cli.command.input_path();
-    let file_buf = get_file(input_path)?;
+    let parser_settings = cli.parser_settings()?;
 
     let mut output_fd = if cli.command.output_path() == "-" {
         OutputType::Stdout(std::io::stdout().lock())
@@ -92,17 +106,36 @@ fn main() -> anyhow::Result<()> {
     };
 
     let command = &cli.command;
-    let backend = command.to_backend_selector();
-    let mut result = backend.process(&file_buf, &mut output_fd);
-
-    match &mut result.err {
-        Some(x) => handle_error(x, &mut result.diagnostics, &file_buf)?,
-        None => {
-            if !cli.quiet {
-                eprintln!("Produced {} diagnostics and no errors", result.diagnostics.len().bold());
-                print_diagnostics(result.diagnostics.iter_mut(), &file_buf);
-            }
-        }
+    let (mut result, (file_buf, source_map)) = if command.is_streaming() {
+        let input = open_file(input_path)?;
+        let result = MidiBackend::process_stream(input, parser_settings, &mut output_fd);
+        // the input is only read again to show where the errors are, which stdin can't do
+        let file_buf = if result.errors.is_empty() || input_path == "-" {
+            (BufLines::from_string(String::new()), SourceMap::default())
+        } else {
+            get_file(input_path)?
+        };
+        (result, file_buf)
+    } else {
+        let (file_buf, source_map) = get_file(input_path)?;
+        let backend = command.to_backend_selector();
+        let result = backend.process_with(&file_buf, parser_settings, &mut output_fd);
+        (result, (file_buf, source_map))
+    };
+
+    // show the input as it was before normalising
+    for err in &mut result.errors {
+        err.main_location = source_map.original_location(&err.main_location);
+    }
+    for diagnostic in &mut result.diagnostics {
+        diagnostic.location = source_map.original_location(&diagnostic.location);
+    }
+    let lines = source_map.original_lines(&file_buf);
+    if !result.errors.is_empty() {
+        handle_errors(&mut result.errors, &mut result.diagnostics, &lines)?
+    } else if !cli.quiet {
+        eprintln!("Produced {} diagnostics and no errors", result.diagnostics.len().bold());
+        print_diagnostics(result.diagnostics.iter_mut(), &lines);
     }
     if !cli.quiet {
         eprintln!("[D]: Performance timings:");
@@ -118,26 +151,37 @@ fn main() -> anyhow::Result<()> {
     if let Some(s) = trace_storage {
         s.finish().unwrap();
     }
-    if result.err.is_some() {
+    if !result.errors.is_empty() {
         std::process::exit(1)
     }
     Ok(())
 }
 
-pub fn handle_error(
-    err: &mut BackendError, diagnostics: &mut [Diagnostic], lines: &impl ParseLines,
+pub fn handle_errors(
+    errors: &mut [BackendError], diagnostics: &mut [Diagnostic], lines: &impl ParseLines,
 ) -> anyhow::Result<()> {
-    let BackendError { ref mut main_location, relevant_lines, kind } = err;
     let diag_count = diagnostics.len();
+    let error_count = match errors.len() {
+        1 => "one error".to_string(),
+        n => format!("{n} errors"),
+    };
 
     eprintln!(
         "Produced {} and {}.",
         format!("{diag_count} diagnostics").bold(),
-        "one error".red().bold(),
+        error_count.red().bold(),
     );
     if diag_count != 0 {
         print_diagnostics(diagnostics.iter_mut(), lines);
     }
+    for err in errors {
+        handle_error(err, lines)?;
+    }
+    Ok(())
+}
+
+pub fn handle_error(err: &mut BackendError, lines: &impl ParseLines) -> anyhow::Result<()> {
+    let BackendError { ref mut main_location, relevant_lines, kind } = err;
 
     let mut location_explainer = String::new();
     main_location.write_location_explainer(&mut location_explainer);
@@ -159,9 +203,11 @@ pub fn handle_error(
             if *e_line_idx as usize != line_idx {
                 continue;
             }
-            let padding =
-                zero_pad_cnt + digit_cnt_usize(line_idx + 1) as usize + 2 + *e_char_idx as usize;
+            let padding = zero_pad_cnt + digit_cnt_usize(line_idx + 1) as usize + 2;
             location_explainer += &" ".repeat(padding);
+            // tabs in the line are repeated, so the caret lines up with the character
+            let line = lines.get_line(line_idx).chars().take(*e_char_idx as usize);
+            location_explainer.extend(line.map(|x| if x == '\t' { '\t' } else { ' ' }));
             writeln!(&mut location_explainer, "{}", "^here".red().bold())?;
         }
     }
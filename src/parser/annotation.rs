@@ -0,0 +1,89 @@
+use std::ops::RangeInclusive;
+
+use super::char_col_in;
+
+/// A playing technique that is written on its own line above or below a part, like `PM----|`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AnnotationKind {
+    PalmMute,
+    LetRing,
+}
+impl AnnotationKind {
+    /// How the annotation is written in the score
+    pub fn label(&self) -> &'static str {
+        match self {
+            AnnotationKind::PalmMute => "P.M.",
+            AnnotationKind::LetRing => "let ring",
+        }
+    }
+    /// Parses a label at the start of `s`, returning the rest of the string
+    fn from_label(s: &str) -> Option<(AnnotationKind, &str)> {
+        use AnnotationKind::*;
+        const LABELS: [(&str, AnnotationKind); 7] = [
+            ("palm mute", PalmMute),
+            ("let ring", LetRing),
+            ("let-ring", LetRing),
+            ("p.m", PalmMute),
+            ("pm", PalmMute),
+            ("l.r", LetRing),
+            ("lr", LetRing),
+        ];
+        LABELS.iter().find_map(|(label, kind)| {
+            let rest = s.get(label.len()..)?;
+            let is_label = s[..label.len()].eq_ignore_ascii_case(label)
+                && !rest.starts_with(|x: char| x.is_alphanumeric());
+            is_label.then_some((*kind, rest))
+        })
+    }
+}
+
+/// A technique applied to a range of ticks. A tick is `string_count` consecutive elements of the
+/// tick stream, so the n-th tick starts at stream index `n * string_count`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Annotation {
+    pub kind: AnnotationKind,
+    pub ticks: RangeInclusive<u32>,
+}
+
+/// Parses a line like `  PM-----|  let ring ---`. Returns the kind and the columns covered by
+/// each annotation, or `None` if the line contains anything else.
+pub fn annotation_line(line: &str) -> Option<Vec<(AnnotationKind, RangeInclusive<usize>)>> {
+    let mut annotations = vec![];
+    let mut rem = line;
+    loop {
+        let trimmed = rem.trim_start_matches([' ', '|']);
+        if trimmed.trim_end().is_empty() {
+            break;
+        }
+        let start = char_col_in(line, trimmed);
+        let (kind, mut rest) = AnnotationKind::from_label(trimmed)?;
+        let mut end = char_col_in(line, rest) - 1;
+        // the annotation lasts until the last dash, possibly spaced out like `P.M. - - -`
+        loop {
+            let next = rest.trim_start_matches(' ');
+            if !next.starts_with(['-', '.', '_', '|']) {
+                break;
+            }
+            end = char_col_in(line, next);
+            rest = &next[1..];
+        }
+        annotations.push((kind, start..=end));
+        rem = rest;
+    }
+    (!annotations.is_empty()).then_some(annotations)
+}
+
+#[test]
+fn test_annotation_line() {
+    use AnnotationKind::*;
+    assert_eq!(annotation_line("  PM----|"), Some(vec![(PalmMute, 2..=8)]));
+    assert_eq!(annotation_line("let ring - - -"), Some(vec![(LetRing, 0..=13)]));
+    assert_eq!(
+        annotation_line("  P.M.--  l.r.---"),
+        Some(vec![(PalmMute, 2..=7), (LetRing, 10..=16)])
+    );
+    assert_eq!(annotation_line("  PM"), Some(vec![(PalmMute, 2..=3)]));
+    assert_eq!(annotation_line("PMx---"), None);
+    assert_eq!(annotation_line("PM--- then something"), None);
+    assert_eq!(annotation_line("   "), None);
+}
@@ -0,0 +1,133 @@
+use std::fmt::Display;
+
+use super::char_col_in;
+
+/// A note name in a chord symbol, like the `Bb` of `Bbm7`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChordNote {
+    pub step: char,
+    /// -1 for flat, 1 for sharp
+    pub alter: i8,
+}
+impl ChordNote {
+    fn parse(s: &str) -> Option<(&str, ChordNote)> {
+        let step = s.chars().next().filter(|x| matches!(x, 'A'..='G'))?;
+        let (rest, alter) = match s.as_bytes().get(1) {
+            Some(b'#') => (&s[2..], 1),
+            Some(b'b') => (&s[2..], -1),
+            _ => (&s[1..], 0),
+        };
+        Some((rest, ChordNote { step, alter }))
+    }
+}
+impl Display for ChordNote {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let accidental = match self.alter {
+            1 => "#",
+            -1 => "b",
+            _ => "",
+        };
+        write!(f, "{}{accidental}", self.step)
+    }
+}
+
+/// The chord suffixes we understand, and the MusicXML `<kind>` they stand for
+const KINDS: [(&str, &str); 28] = [
+    ("", "major"),
+    ("maj", "major"),
+    ("M", "major"),
+    ("m", "minor"),
+    ("min", "minor"),
+    ("-", "minor"),
+    ("7", "dominant"),
+    ("maj7", "major-seventh"),
+    ("M7", "major-seventh"),
+    ("m7", "minor-seventh"),
+    ("min7", "minor-seventh"),
+    ("mmaj7", "major-minor"),
+    ("mM7", "major-minor"),
+    ("dim", "diminished"),
+    ("dim7", "diminished-seventh"),
+    ("m7b5", "half-diminished"),
+    ("aug", "augmented"),
+    ("+", "augmented"),
+    ("sus2", "suspended-second"),
+    ("sus4", "suspended-fourth"),
+    ("sus", "suspended-fourth"),
+    ("5", "power"),
+    ("6", "major-sixth"),
+    ("m6", "minor-sixth"),
+    ("9", "dominant-ninth"),
+    ("maj9", "major-ninth"),
+    ("m9", "minor-ninth"),
+    ("11", "dominant-11th"),
+];
+
+/// A chord symbol like `G/B`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chord {
+    pub root: ChordNote,
+    /// The suffix as written, like `m7`
+    pub suffix: &'static str,
+    /// The MusicXML `<kind>` of the chord, like `minor-seventh`
+    pub kind: &'static str,
+    pub bass: Option<ChordNote>,
+}
+impl Chord {
+    pub fn parse(s: &str) -> Option<Chord> {
+        let (main, bass) = match s.split_once('/') {
+            Some((main, bass)) => {
+                let (rest, bass) = ChordNote::parse(bass)?;
+                if !rest.is_empty() {
+                    return None;
+                }
+                (main, Some(bass))
+            }
+            None => (s, None),
+        };
+        let (suffix, root) = ChordNote::parse(main)?;
+        let (suffix, kind) = KINDS.iter().find(|x| x.0 == suffix)?;
+        Some(Chord { root, suffix, kind, bass })
+    }
+}
+impl Display for Chord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.root, self.suffix)?;
+        match self.bass {
+            Some(bass) => write!(f, "/{bass}"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Parses a line of chord symbols above a part, like `   Am        G/B     C`.
+/// Returns the column of each chord.
+pub fn chord_line(line: &str) -> Option<Vec<(usize, Chord)>> {
+    let mut chords = vec![];
+    let mut rem = line;
+    loop {
+        let trimmed = rem.trim_start_matches([' ', '|']);
+        if trimmed.trim_end().is_empty() {
+            break;
+        }
+        let col = char_col_in(line, trimmed);
+        let token_len = trimmed.find([' ', '|']).unwrap_or(trimmed.len());
+        chords.push((col, Chord::parse(&trimmed[..token_len])?));
+        rem = &trimmed[token_len..];
+    }
+    (!chords.is_empty()).then_some(chords)
+}
+
+#[test]
+fn test_chord_line() {
+    let line = chord_line("   Am        G/B     C#m7b5 |Bb").unwrap();
+    let cols: Vec<_> = line.iter().map(|x| x.0).collect();
+    assert_eq!(cols, [3, 13, 21, 29]);
+    let names: Vec<_> = line.iter().map(|x| x.1.to_string()).collect();
+    assert_eq!(names, ["Am", "G/B", "C#m7b5", "Bb"]);
+    let kinds: Vec<_> = line.iter().map(|x| x.1.kind).collect();
+    assert_eq!(kinds, ["minor", "major", "half-diminished", "major"]);
+    assert_eq!(line[1].1.bass, Some(ChordNote { step: 'B', alter: 0 }));
+    assert_eq!(chord_line("A Day in the Life"), None);
+    assert_eq!(chord_line("G/"), None);
+}
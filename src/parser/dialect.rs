@@ -0,0 +1,245 @@
+use std::fmt::Display;
+
+/// A technique that a [Dialect] has a symbol for
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Technique {
+    DeadNote,
+    Bend,
+    PreBend,
+    HammerOn,
+    PullOff,
+    Release,
+    SlideUp,
+    SlideDown,
+    Vibrato,
+    Tap,
+    Trill,
+    Tremolo,
+    /// Tremolo picking that is written out as `TP`, and written that way again by dumps
+    TremoloTp,
+    Accent,
+    Staccato,
+}
+impl Technique {
+    pub const ALL: [Technique; 15] = [
+        Technique::DeadNote,
+        Technique::Bend,
+        Technique::PreBend,
+        Technique::HammerOn,
+        Technique::PullOff,
+        Technique::Release,
+        Technique::SlideUp,
+        Technique::SlideDown,
+        Technique::Vibrato,
+        Technique::Tap,
+        Technique::Trill,
+        Technique::Tremolo,
+        Technique::TremoloTp,
+        Technique::Accent,
+        Technique::Staccato,
+    ];
+    /// The name of the technique in dialect files
+    pub fn name(self) -> &'static str {
+        match self {
+            Technique::DeadNote => "dead-note",
+            Technique::Bend => "bend",
+            Technique::PreBend => "pre-bend",
+            Technique::HammerOn => "hammer-on",
+            Technique::PullOff => "pull-off",
+            Technique::Release => "release",
+            Technique::SlideUp => "slide-up",
+            Technique::SlideDown => "slide-down",
+            Technique::Vibrato => "vibrato",
+            Technique::Tap => "tap",
+            Technique::Trill => "trill",
+            Technique::Tremolo => "tremolo",
+            Technique::TremoloTp => "tremolo-tp",
+            Technique::Accent => "accent",
+            Technique::Staccato => "staccato",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Technique> {
+        Technique::ALL.into_iter().find(|x| x.name() == name)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DialectError {
+    /// There is no built-in dialect with this name
+    UnknownDialect(String),
+    /// Symbols can't be empty, contain whitespace or `|`, or start like a fret, a rest or a
+    /// harmonic
+    InvalidSymbol(String),
+    UnknownTechnique(String),
+    /// A line of a dialect file that isn't a symbol followed by a technique
+    InvalidLine(String),
+}
+impl Display for DialectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DialectError::UnknownDialect(x) => write!(
+                f,
+                "There is no dialect called `{x}`. The built-in ones are {}.",
+                Dialect::BUILT_IN.join(", ")
+            ),
+            DialectError::InvalidSymbol(x) => write!(f, "`{x}` can't be used as a symbol"),
+            DialectError::UnknownTechnique(x) => write!(
+                f,
+                "There is no technique called `{x}`. The known ones are {}.",
+                Technique::ALL.map(Technique::name).join(", ")
+            ),
+            DialectError::InvalidLine(x) => {
+                write!(f, "Expected a symbol and a technique, like `b bend`, found `{x}`")
+            }
+        }
+    }
+}
+impl std::error::Error for DialectError {}
+
+/// The symbols that a tab uses for techniques, like `b` or `^` for bends. Frets, rests and the
+/// brackets of harmonics and ghost notes are the same in every dialect.
+///
+/// Dialects can be built in code with [Dialect::insert], or read from a file with
+/// [Dialect::parse].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dialect {
+    /// Longer symbols come first, so that `tr` is found before `t`
+    symbols: Vec<(String, Technique)>,
+}
+impl Default for Dialect {
+    fn default() -> Self {
+        Self::standard()
+    }
+}
+impl Dialect {
+    /// The names of the built-in dialects, see [Dialect::built_in]
+    pub const BUILT_IN: [&str; 3] = ["standard", "ultimate-guitar", "classic"];
+
+    /// A dialect without any symbols
+    pub fn empty() -> Self {
+        Dialect { symbols: vec![] }
+    }
+    /// The symbols that are understood by default
+    pub fn standard() -> Self {
+        use Technique::*;
+        Self::from_valid(&[
+            ("x", DeadNote),
+            ("b", Bend),
+            ("pb", PreBend),
+            ("h", HammerOn),
+            ("p", PullOff),
+            ("r", Release),
+            ("/", SlideUp),
+            ("\\", SlideDown),
+            ("~", Vibrato),
+            ("t", Tap),
+            ("tr", Trill),
+            ("=", Tremolo),
+            ("TP", TremoloTp),
+        ])
+    }
+    /// The standard symbols, and the ones of the legend of Ultimate Guitar: `s` and `S` for
+    /// slides and `T` for taps.
+    pub fn ultimate_guitar() -> Self {
+        use Technique::*;
+        let mut dialect = Self::standard();
+        dialect.extend_valid(&[("s", SlideUp), ("S", SlideUp), ("T", Tap)]);
+        dialect
+    }
+    /// The standard symbols, and the ones of older text tabs: `^` for bends, `v` for vibrato, `s`
+    /// for slides, `X` for dead notes, `*` for accents and `.` for staccato.
+    pub fn classic() -> Self {
+        use Technique::*;
+        let mut dialect = Self::standard();
+        dialect.extend_valid(&[
+            ("^", Bend),
+            ("v", Vibrato),
+            ("s", SlideUp),
+            ("X", DeadNote),
+            ("*", Accent),
+            (".", Staccato),
+        ]);
+        dialect
+    }
+    /// The built-in dialect called `name`, one of [Dialect::BUILT_IN]
+    pub fn built_in(name: &str) -> Result<Self, DialectError> {
+        match name {
+            "standard" => Ok(Self::standard()),
+            "ultimate-guitar" => Ok(Self::ultimate_guitar()),
+            "classic" => Ok(Self::classic()),
+            _ => Err(DialectError::UnknownDialect(name.to_string())),
+        }
+    }
+    /// Reads a dialect file. Every line is a symbol and the name of its technique, like
+    /// `^ bend`. The first line can be `extends <dialect>` to start from a built-in dialect
+    /// instead of an empty one. Lines starting with `#` are comments.
+    pub fn parse(text: &str) -> Result<Self, DialectError> {
+        let lines = text.lines().map(str::trim).filter(|x| !x.is_empty() && !x.starts_with('#'));
+        let mut lines = lines.peekable();
+        let mut dialect = match lines.next_if(|x| x.starts_with("extends ")) {
+            Some(line) => Self::built_in(line["extends ".len()..].trim())?,
+            None => Self::empty(),
+        };
+        for line in lines {
+            let mut words = line.split_whitespace();
+            let (Some(symbol), Some(name), None) = (words.next(), words.next(), words.next())
+            else {
+                return Err(DialectError::InvalidLine(line.to_string()));
+            };
+            let technique = Technique::from_name(name)
+                .ok_or_else(|| DialectError::UnknownTechnique(name.to_string()))?;
+            dialect.insert(symbol, technique)?;
+        }
+        Ok(dialect)
+    }
+    /// Makes `symbol` stand for `technique`, replacing what it meant before.
+    pub fn insert(&mut self, symbol: &str, technique: Technique) -> Result<(), DialectError> {
+        let valid =
+            symbol.chars().next().is_some_and(|x| !matches!(x, '0'..='9' | '-' | '(' | '<' | '['))
+                && !symbol.contains(|x: char| x == '|' || x.is_whitespace());
+        if !valid {
+            return Err(DialectError::InvalidSymbol(symbol.to_string()));
+        }
+        self.symbols.retain(|x| x.0 != symbol);
+        let idx = self.symbols.partition_point(|x| x.0.len() >= symbol.len());
+        self.symbols.insert(idx, (symbol.to_string(), technique));
+        Ok(())
+    }
+    /// The technique whose symbol `s` starts with, and the rest of `s`
+    pub fn technique<'a>(&self, s: &'a str) -> Option<(&'a str, Technique)> {
+        let (symbol, technique) = self.symbols.iter().find(|x| s.starts_with(x.0.as_str()))?;
+        Some((&s[symbol.len()..], *technique))
+    }
+    /// The symbols of a built-in dialect, which are known to be valid
+    fn from_valid(symbols: &[(&str, Technique)]) -> Self {
+        let mut dialect = Self::empty();
+        dialect.extend_valid(symbols);
+        dialect
+    }
+    fn extend_valid(&mut self, symbols: &[(&str, Technique)]) {
+        for (symbol, technique) in symbols {
+            self.insert(symbol, *technique).expect("built-in symbols are valid");
+        }
+    }
+}
+
+#[test]
+fn test_dialect() {
+    let dialect = Dialect::standard();
+    assert_eq!(dialect.technique("tr-"), Some(("-", Technique::Trill)));
+    assert_eq!(dialect.technique("t-"), Some(("-", Technique::Tap)));
+    assert_eq!(dialect.technique("TP-"), Some(("-", Technique::TremoloTp)));
+    assert_eq!(dialect.technique("^9"), None);
+
+    let file = "# the symbols of some website\nextends standard\n\n^ bend\nX dead-note\n";
+    let dialect = Dialect::parse(file).unwrap();
+    assert_eq!(dialect.technique("^9"), Some(("9", Technique::Bend)));
+    assert_eq!(dialect.technique("b9"), Some(("9", Technique::Bend)));
+    assert_eq!(dialect.technique("X"), Some(("", Technique::DeadNote)));
+    assert_eq!(Dialect::parse("s slide"), Err(DialectError::UnknownTechnique("slide".into())));
+    assert_eq!(Dialect::parse("5 bend"), Err(DialectError::InvalidSymbol("5".into())));
+    assert_eq!(
+        Dialect::parse("extends tabsite"),
+        Err(DialectError::UnknownDialect("tabsite".into()))
+    );
+}
@@ -0,0 +1,67 @@
+use std::ops::Range;
+
+use super::{annotation::annotation_line, lines_above, string_line, ParserResult};
+use crate::ParseLines;
+
+/// Some lines of a score replaced with new text, for [super::Parser::reparse].
+#[derive(Clone, Debug)]
+pub struct TextEdit {
+    /// The lines that were replaced, as they were numbered before the edit
+    pub lines: Range<usize>,
+    /// The lines they were replaced with. An empty text removes the lines.
+    pub text: String,
+}
+impl TextEdit {
+    /// The number of lines that took the place of the replaced ones
+    pub fn inserted_lines(&self) -> usize {
+        self.text.lines().count()
+    }
+}
+
+/// The result of parsing a score before an edit, and where the edit is.
+#[derive(Debug)]
+pub(crate) struct PreviousParse {
+    pub result: ParserResult,
+    /// The lines of the edited score that came from the edit
+    edited: Range<usize>,
+    /// The number of lines that were replaced
+    removed: usize,
+}
+impl PreviousParse {
+    pub fn new(result: ParserResult, edit: &TextEdit) -> Self {
+        let start = edit.lines.start;
+        let edited = start..start + edit.inserted_lines();
+        Self { result, edited, removed: edit.lines.len() }
+    }
+    /// Finds the part of the previous result that is the same as the part at `part_first_line` of
+    /// the edited score. This is the case if the part and the lines around it that are read along
+    /// with it are all outside of the edit.
+    pub fn reusable_part<L: ParseLines>(
+        &self, lines: &L, part_first_line: usize, string_count: usize,
+    ) -> Option<usize> {
+        if string_count != self.result.string_count {
+            return None;
+        }
+        let above = lines_above(lines, part_first_line).start;
+        let mut below = part_first_line + string_count;
+        while below < lines.line_count() && annotation_line(lines.get_line(below)).is_some() {
+            below += 1;
+        }
+        // the line after the annotations is read for lyrics. One more line on each side decides
+        // where the lines that belong to the part end.
+        let (first, last) = (above.saturating_sub(1), below + 1);
+        let Range { start, end } = self.edited;
+        if first < end && start <= last {
+            return None;
+        }
+        let old_line = match part_first_line >= end {
+            true => part_first_line - end + start + self.removed,
+            false => part_first_line,
+        };
+        let part_idx =
+            self.result.offsets.binary_search_by_key(&(old_line as u32), |x| x.0).ok()?;
+        // a part with a stray line reaches further down than the lines that were checked
+        let (line, last) = (old_line as u32, string_count as u32 - 1);
+        (string_line(&self.result.stray_lines, line, last) == line + last).then_some(part_idx)
+    }
+}
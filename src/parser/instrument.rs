@@ -0,0 +1,131 @@
+use crate::ParseLines;
+
+/// The words that name an instrument in a label
+const INSTRUMENTS: [&str; 7] = ["gtr", "guitar", "bass", "uke", "ukulele", "banjo", "mandolin"];
+/// The words that can come along with the instrument, like the `Lead` of `Lead Gtr`
+const QUALIFIERS: [&str; 5] = ["lead", "rhythm", "acoustic", "electric", "clean"];
+
+/// Parses the label of an instrument on its own line, like `Gtr I`, `Guitar 2:` or `Bass`. Besides
+/// the instrument, it can only have a number, a roman numeral or a letter, and words like `Lead`.
+/// Returns the label without the colon.
+pub fn instrument_label(line: &str) -> Option<&str> {
+    let label = line.trim();
+    let label = label.strip_suffix(':').unwrap_or(label).trim_end();
+    let words: Vec<String> =
+        label.split_whitespace().map(|x| x.trim_end_matches('.').to_ascii_lowercase()).collect();
+    if words.is_empty() || words.len() > 3 {
+        return None;
+    }
+    let is_instrument = |x: &String| INSTRUMENTS.contains(&x.as_str());
+    let is_number = |x: &String| {
+        x.bytes().all(|x| x.is_ascii_digit())
+            || x.bytes().all(|x| matches!(x, b'i' | b'v' | b'x'))
+            || x.len() == 1 && x.bytes().all(|x| x.is_ascii_lowercase())
+    };
+    let valid = words.iter().filter(|x| is_instrument(x)).count() >= 1
+        && (words.iter()).all(|x| is_instrument(x) || is_number(x) || QUALIFIERS.contains(&&**x));
+    valid.then_some(label)
+}
+
+/// Which instrument each line of a score belongs to. A label belongs to the parts after it, until
+/// the next label, and so do the lines around those parts, from the label or the lines above the
+/// part to the end of the lines under it. The other lines, like directives, belong to every
+/// instrument.
+#[derive(Debug, Default)]
+pub(crate) struct InstrumentLines {
+    /// The name of every instrument, in the order they first appear. The parts before the first
+    /// label are of an instrument without a name.
+    pub names: Vec<Option<String>>,
+    /// The instrument of every line, `None` if it belongs to all of them
+    owners: Vec<Option<usize>>,
+}
+impl InstrumentLines {
+    pub fn find<L: ParseLines>(lines: &L, is_string_line: impl Fn(&str) -> bool) -> Self {
+        let mut this = Self { names: vec![], owners: vec![None; lines.line_count()] };
+        // the current instrument, and the line of its label if no part came after it yet
+        let (mut current, mut label_line) = (None, None);
+        let mut line_idx = 0;
+        while line_idx < lines.line_count() {
+            let line = lines.get_line(line_idx);
+            if let Some(label) = instrument_label(line) {
+                let key = |x: &str| x.split_whitespace().collect::<Vec<_>>().join(" ");
+                let known = (this.names.iter()).position(|x| {
+                    x.as_deref().is_some_and(|x| key(x).eq_ignore_ascii_case(&key(label)))
+                });
+                current = Some(known.unwrap_or_else(|| {
+                    this.names.push(Some(label.to_string()));
+                    this.names.len() - 1
+                }));
+                label_line = Some(line_idx);
+                line_idx += 1;
+                continue;
+            }
+            if !is_string_line(line) {
+                line_idx += 1;
+                continue;
+            }
+            let instrument = *current.get_or_insert_with(|| {
+                this.names.push(None);
+                this.names.len() - 1
+            });
+            let first =
+                label_line.take().unwrap_or_else(|| super::lines_above(lines, line_idx).start);
+            // the string lines, and the annotations and lyrics under them, up to an empty line
+            let mut end = line_idx;
+            while end < lines.line_count() && is_string_line(lines.get_line(end)) {
+                end += 1;
+            }
+            while end < lines.line_count() {
+                let line = lines.get_line(end);
+                if line.trim().is_empty()
+                    || instrument_label(line).is_some()
+                    || is_string_line(line)
+                {
+                    break;
+                }
+                end += 1;
+            }
+            this.owners[first..end].fill(Some(instrument));
+            line_idx = end;
+        }
+        this
+    }
+    /// The lines of one instrument. The lines of the others read as empty.
+    pub fn view<'a, L: ParseLines>(
+        &'a self, lines: &'a L, instrument: usize,
+    ) -> InstrumentView<'a, L> {
+        InstrumentView { lines, owners: &self.owners, instrument }
+    }
+}
+
+/// The lines of a score as one of its instruments sees them, see [InstrumentLines::view]
+pub(crate) struct InstrumentView<'a, L> {
+    lines: &'a L,
+    owners: &'a [Option<usize>],
+    instrument: usize,
+}
+impl<L: ParseLines> ParseLines for InstrumentView<'_, L> {
+    fn get_line(&self, idx: usize) -> &str {
+        match self.owners[idx] {
+            Some(owner) if owner != self.instrument => "",
+            _ => self.lines.get_line(idx),
+        }
+    }
+
+    fn line_count(&self) -> usize {
+        self.lines.line_count()
+    }
+}
+
+#[test]
+fn test_instrument_label() {
+    assert_eq!(instrument_label("Gtr I"), Some("Gtr I"));
+    assert_eq!(instrument_label("  Gtr. II:  "), Some("Gtr. II"));
+    assert_eq!(instrument_label("Bass"), Some("Bass"));
+    assert_eq!(instrument_label("Lead Guitar 2"), Some("Lead Guitar 2"));
+    assert_eq!(instrument_label("Guitar B"), Some("Guitar B"));
+    assert_eq!(instrument_label("Guitar tabs by somebody"), None);
+    assert_eq!(instrument_label("Guitar Solo"), None);
+    assert_eq!(instrument_label("Am  G"), None);
+    assert_eq!(instrument_label(""), None);
+}
@@ -0,0 +1,80 @@
+use super::char_col_in;
+
+/// Where a syllable is in its word, like MusicXML's `<syllabic>`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Syllabic {
+    Single,
+    Begin,
+    Middle,
+    End,
+}
+impl Syllabic {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Syllabic::Single => "single",
+            Syllabic::Begin => "begin",
+            Syllabic::Middle => "middle",
+            Syllabic::End => "end",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Syllable {
+    pub text: String,
+    pub syllabic: Syllabic,
+}
+
+/// Parses a line of lyrics under a part, like `Hel - lo  world`. Words are split into syllables
+/// by hyphens, and every syllable is placed at its own column.
+pub fn lyric_line(line: &str) -> Vec<(usize, Syllable)> {
+    // the column and text of each syllable, and whether it is joined to the previous one
+    let mut syllables: Vec<(usize, &str, bool)> = vec![];
+    let mut hyphen = false;
+    let mut rem = line;
+    loop {
+        let trimmed = rem.trim_start_matches(' ');
+        let Some(first) = trimmed.chars().next() else { break };
+        if first == '-' {
+            hyphen = true;
+            rem = &trimmed[1..];
+            continue;
+        }
+        let col = char_col_in(line, trimmed);
+        let len = trimmed.find([' ', '-']).unwrap_or(trimmed.len());
+        syllables.push((col, &trimmed[..len], hyphen && !syllables.is_empty()));
+        hyphen = false;
+        rem = &trimmed[len..];
+    }
+    let joined_to_next = |idx: usize| syllables.get(idx + 1).is_some_and(|x| x.2);
+    let syllables = syllables.iter().enumerate().map(|(idx, (col, text, joined_to_prev))| {
+        let syllabic = match (joined_to_prev, joined_to_next(idx)) {
+            (false, false) => Syllabic::Single,
+            (false, true) => Syllabic::Begin,
+            (true, true) => Syllabic::Middle,
+            (true, false) => Syllabic::End,
+        };
+        (*col, Syllable { text: text.to_string(), syllabic })
+    });
+    syllables.collect()
+}
+
+#[test]
+fn test_lyric_line() {
+    use Syllabic::*;
+    let line = lyric_line("  Hel - lo  won-der-ful world");
+    let found: Vec<_> = line.iter().map(|(col, x)| (*col, x.text.as_str(), x.syllabic)).collect();
+    assert_eq!(
+        found,
+        [
+            (2, "Hel", Begin),
+            (8, "lo", End),
+            (12, "won", Begin),
+            (16, "der", Middle),
+            (20, "ful", End),
+            (24, "world", Single)
+        ]
+    );
+    let cols: Vec<_> = lyric_line("Ça  va").into_iter().map(|x| x.0).collect();
+    assert_eq!(cols, [0, 4]);
+}
@@ -0,0 +1,91 @@
+use super::numeric;
+
+/// Information about the score, read from `key: value` lines outside of parts, like
+/// ```md
+/// title: Spanish Romance
+/// tempo: 90
+/// capo: 2
+/// time: 3/4
+/// ```
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ScoreMetadata {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    /// In quarter notes per minute
+    pub tempo: Option<u32>,
+    /// The fret the capo is placed on. Frets in the tab are relative to the capo.
+    pub capo: Option<u8>,
+    /// The tuning as written in the header. The resolved tuning is [super::ParserResult::tuning].
+    pub tuning: Option<String>,
+    /// Beats and beat type
+    pub time: Option<(u8, u8)>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MetadataKey {
+    Title,
+    Artist,
+    Tempo,
+    Capo,
+    Tuning,
+    Time,
+}
+
+impl MetadataKey {
+    pub fn name(&self) -> &'static str {
+        match self {
+            MetadataKey::Title => "title",
+            MetadataKey::Artist => "artist",
+            MetadataKey::Tempo => "tempo",
+            MetadataKey::Capo => "capo",
+            MetadataKey::Tuning => "tuning",
+            MetadataKey::Time => "time",
+        }
+    }
+    pub fn from_key(key: &str) -> Option<Self> {
+        match key.trim().to_ascii_lowercase().as_str() {
+            "title" | "song" => Some(Self::Title),
+            "artist" | "band" | "composer" => Some(Self::Artist),
+            "tempo" | "bpm" => Some(Self::Tempo),
+            "capo" => Some(Self::Capo),
+            "tuning" => Some(Self::Tuning),
+            "time" | "time signature" => Some(Self::Time),
+            _ => None,
+        }
+    }
+}
+
+impl ScoreMetadata {
+    /// Stores `value` under `key`. Returns `None` if the value doesn't make sense for this key.
+    pub fn set(&mut self, key: &MetadataKey, value: &str) -> Option<()> {
+        let value = value.trim();
+        match key {
+            MetadataKey::Title => self.title = Some(value.to_string()),
+            MetadataKey::Artist => self.artist = Some(value.to_string()),
+            // allow things like `120 bpm`
+            MetadataKey::Tempo => {
+                let digits = value.bytes().take_while(u8::is_ascii_digit).count();
+                let tempo: u32 = value[..digits].parse().ok()?;
+                if tempo == 0 {
+                    return None;
+                }
+                self.tempo = Some(tempo);
+            }
+            MetadataKey::Capo => {
+                let none = value.eq_ignore_ascii_case("none") || value.eq_ignore_ascii_case("no");
+                self.capo = if none { None } else { Some(numeric(value).ok()?.1) };
+            }
+            MetadataKey::Tuning => self.tuning = Some(value.to_string()),
+            MetadataKey::Time => {
+                let (rem, beats) = numeric(value).ok()?;
+                let rem = rem.trim_start().strip_prefix('/')?.trim_start();
+                let (rem, beat_type) = numeric(rem).ok()?;
+                if beats == 0 || !beat_type.is_power_of_two() || !rem.trim().is_empty() {
+                    return None;
+                }
+                self.time = Some((beats, beat_type));
+            }
+        }
+        Some(())
+    }
+}
@@ -1,8 +1,24 @@
+#[allow(clippy::module_inception)]
 mod parser;
 pub use parser::*;
+pub mod annotation;
+pub mod chord;
+pub mod dialect;
+pub mod incremental;
+pub mod instrument;
+pub mod lyrics;
+pub mod metadata;
+pub mod normalize;
 #[cfg(test)]
 mod parser_tests;
+pub mod rhythm;
+pub mod section;
+pub mod settings;
+pub mod spans;
+pub mod stream;
 pub(crate) mod tab_element;
+pub mod trailing;
+pub mod tuning;
 
 pub fn char(c: char) -> impl Fn(&str) -> Result<(&str, char), &str> {
     move |s: &str| match s.chars().next() {
@@ -11,11 +27,11 @@ pub fn char(c: char) -> impl Fn(&str) -> Result<(&str, char), &str> {
     }
 }
 
-pub fn string_name(s: &str) -> Result<(&str, char), &str> {
-    match s.chars().next() {
-        Some(c) if c.is_alphabetic() => Ok((&s[1..], c)),
-        _ => Err(s),
-    }
+/// The column of `rest` in `line`, counted in characters like an editor does. The columns of
+/// ticks, of the lines around parts, of spans and of errors are all counted this way. `rest` must
+/// be a subslice of `line`.
+fn char_col_in(line: &str, rest: &str) -> usize {
+    line[..rest.as_ptr() as usize - line.as_ptr() as usize].chars().count()
 }
 
 #[inline(always)]
@@ -24,8 +40,7 @@ fn numeric(s: &str) -> Result<(&str, u8), &str> {
     let mut i = 0;
     let mut sum: u8 = 0;
     while i < bytes.len() && bytes[i].is_ascii_digit() {
-        sum = sum.checked_mul(10).ok_or(s)?;
-        sum += bytes[i] - b'0';
+        sum = sum.checked_mul(10).and_then(|x| x.checked_add(bytes[i] - b'0')).ok_or(s)?;
         i += 1;
     }
     if i == 0 {
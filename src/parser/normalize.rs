@@ -0,0 +1,156 @@
+use std::{borrow::Cow, ops::Range};
+
+use crate::{backend::errors::error_location::ErrorLocation, BufLines, ParseLines};
+
+/// Characters that copy-pasted tabs use instead of `-`
+const DASHES: [char; 10] = ['‐', '‑', '‒', '–', '—', '―', '−', '─', '━', '┄'];
+/// Characters that copy-pasted tabs use instead of `|`
+const BARLINES: [char; 7] = ['│', '┃', '║', '┆', '┊', '╎', '｜'];
+/// Tab characters are expanded to spaces up to the next multiple of this
+const TAB_WIDTH: usize = 8;
+
+/// A line that was changed by normalising, as it was in the input.
+#[derive(Clone, Debug)]
+struct OriginalLine {
+    line_idx: usize,
+    text: String,
+    /// The character of `text` that each character of the normalised line came from, and the
+    /// number of characters of `text` for the end of the line
+    cols: Vec<u32>,
+}
+
+/// Where the lines of a normalised input came from, so that errors and spans can point at the
+/// input. Only the lines that normalising changed are stored. Columns count characters, both in
+/// the normalised input and in the original one.
+#[derive(Clone, Debug, Default)]
+pub struct SourceMap {
+    lines: Vec<OriginalLine>,
+}
+impl SourceMap {
+    /// Normalises a whole input, see [normalize_line].
+    pub fn normalize(input: &[u8]) -> (BufLines, SourceMap) {
+        let mut buf = String::with_capacity(input.len());
+        let mut map = SourceMap::default();
+        for (line_idx, raw) in input.split(|x| *x == b'\n').enumerate() {
+            if line_idx != 0 {
+                buf.push('\n');
+            }
+            let (line, cols) = normalize_mapped(raw);
+            if let Some(cols) = cols {
+                let text = decode(strip_cr(raw)).into_owned();
+                map.lines.push(OriginalLine { line_idx, text, cols });
+            }
+            buf += &line;
+        }
+        (BufLines::from_string(buf), map)
+    }
+    fn get(&self, line_idx: usize) -> Option<&OriginalLine> {
+        let idx = self.lines.binary_search_by_key(&line_idx, |x| x.line_idx).ok()?;
+        Some(&self.lines[idx])
+    }
+    /// Moves a location in the normalised input to the character of the input it came from.
+    pub fn original_location(&self, location: &ErrorLocation) -> ErrorLocation {
+        let ErrorLocation::LineAndChar(line, char) = *location else { return location.clone() };
+        let Some(original) = self.get(line as usize) else { return location.clone() };
+        let last = original.cols.len() - 1;
+        ErrorLocation::LineAndChar(line, original.cols[(char as usize).min(last)])
+    }
+    /// Moves the characters of a line of the normalised input, like a span from
+    /// [crate::parser::ParserRef::source_span], to the characters of the input they came from. A
+    /// span that covers part of an expanded tab covers the whole tab.
+    pub fn original_span(&self, line: u32, span: Range<usize>) -> Range<usize> {
+        let Some(original) = self.get(line as usize) else { return span };
+        let col = |x: usize| original.cols[x.min(original.cols.len() - 1)] as usize;
+        match span.end > span.start {
+            true => col(span.start)..col(span.end - 1) + 1,
+            false => col(span.start)..col(span.start),
+        }
+    }
+    /// The lines of the input before normalising, given the normalised ones.
+    pub fn original_lines<'a, L: ParseLines>(&'a self, normalized: &'a L) -> OriginalLines<'a, L> {
+        OriginalLines { normalized, map: self }
+    }
+}
+
+/// The lines of an input as they were before normalising, to show them along with errors.
+pub struct OriginalLines<'a, L> {
+    normalized: &'a L,
+    map: &'a SourceMap,
+}
+impl<L: ParseLines> ParseLines for OriginalLines<'_, L> {
+    fn get_line(&self, idx: usize) -> &str {
+        match self.map.get(idx) {
+            Some(original) => &original.text,
+            None => self.normalized.get_line(idx),
+        }
+    }
+
+    fn line_count(&self) -> usize {
+        self.normalized.line_count()
+    }
+}
+
+/// Turns a line of input into one the parser understands: the input is read as UTF-8, or as
+/// Latin-1 if it isn't valid UTF-8, a trailing `\r` is removed, tab characters are expanded to
+/// spaces and the dashes and box-drawing lines of copy-pasted tabs become `-` and `|`.
+pub fn normalize_line(raw: &[u8]) -> String {
+    normalize_mapped(raw).0
+}
+
+/// Like [normalize_line], but also returns the column each character came from, unless every
+/// character is where it was.
+fn normalize_mapped(raw: &[u8]) -> (String, Option<Vec<u32>>) {
+    let decoded = decode(strip_cr(raw));
+    if decoded.is_ascii() && !decoded.contains('\t') {
+        return (decoded.into_owned(), None);
+    }
+    let mut line = String::with_capacity(decoded.len());
+    let mut cols = Vec::with_capacity(decoded.len() + 1);
+    // tab stops are counted in characters, not bytes
+    let mut width = 0;
+    let mut char_count = 0;
+    for (col, c) in decoded.chars().enumerate() {
+        let (c, repeat) = match c {
+            '\t' => (' ', TAB_WIDTH - width % TAB_WIDTH),
+            c if DASHES.contains(&c) => ('-', 1),
+            c if BARLINES.contains(&c) => ('|', 1),
+            c => (c, 1),
+        };
+        for _ in 0..repeat {
+            line.push(c);
+            cols.push(col as u32);
+        }
+        width += repeat;
+        char_count = col + 1;
+    }
+    cols.push(char_count as u32);
+    (line, Some(cols))
+}
+
+fn strip_cr(raw: &[u8]) -> &[u8] {
+    raw.strip_suffix(b"\r").unwrap_or(raw)
+}
+
+fn decode(raw: &[u8]) -> Cow<'_, str> {
+    match std::str::from_utf8(raw) {
+        Ok(x) => Cow::Borrowed(x),
+        Err(_) => Cow::Owned(raw.iter().map(|x| *x as char).collect()),
+    }
+}
+
+#[test]
+fn test_normalize() {
+    let input = "e|–0—|\r\nB│-1-│\r\n\tAm\n  Caf\u{e9}\n".as_bytes();
+    let (lines, map) = SourceMap::normalize(input);
+    assert_eq!(lines.get_line(0), "e|-0-|");
+    assert_eq!(lines.get_line(1), "B|-1-|");
+    assert_eq!(lines.get_line(2), "        Am");
+    assert_eq!(lines.get_line(3), "  Café");
+    // the `A` comes after a tab that was expanded to eight spaces
+    let location = map.original_location(&ErrorLocation::LineAndChar(2, 8));
+    assert_eq!(location, ErrorLocation::LineAndChar(2, 1));
+    assert_eq!(map.original_lines(&lines).get_line(2), "\tAm");
+
+    // Latin-1 is read as such when the line isn't valid UTF-8
+    assert_eq!(normalize_line(b"Caf\xe9"), "Café");
+}
@@ -1,70 +1,296 @@
-use tracing::{debug, debug_span, span, trace, trace_span, Level};
+use tracing::{debug, debug_span, span, trace, Level};
 
 use super::{
-    string_name,
+    annotation::{annotation_line, Annotation},
+    char_col_in,
+    chord::{chord_line, Chord},
+    incremental::{PreviousParse, TextEdit},
+    instrument::{instrument_label, InstrumentLines},
+    lyrics::{lyric_line, Syllable},
+    metadata::{MetadataKey, ScoreMetadata},
+    rhythm::{rhythm_line, Rhythm},
+    section::section_label,
+    settings::ParserSettings,
+    spans::ElementSpan,
     tab_element::{self, tab_element3, TabElement},
+    trailing::{strip_trailing, Trailing, TrailingAnnotation},
+    tuning::{note_name, resolve_tuning, standard_tuning, tuning_directive, NoteName},
 };
 use crate::{
-    backend::errors::backend_error::BackendError, parser::tab_element::TabElementError, ParseLines,
+    backend::errors::{
+        backend_error::BackendError, diagnostic::Diagnostic, diagnostic_kind::DiagnosticKind,
+        error_location::ErrorLocation,
+    },
+    parser::tab_element::TabElementError,
+    ParseLines,
 };
-use std::{array, ops::RangeInclusive};
+use std::ops::{Range, RangeInclusive};
+
+/// Runs of string lines shorter than this are not considered to be a part.
+pub const MIN_STRING_COUNT: usize = 3;
 
 pub fn line_is_valid(line: &str) -> bool {
     let line = line.trim();
     let mut chars = line.chars();
     let first_is_alphanumeric = chars.next().map(|x| x.is_alphanumeric()).unwrap_or(false);
+    // skip the accidental and octave of string names like `C#2`
+    let mut chars = chars.skip_while(|x| matches!(x, '#' | 'b') || x.is_ascii_digit());
     let second_is_measure_sep = chars.next().map(|x| x == '|').unwrap_or(false);
-    let last_is_measure_end = line.ends_with('|');
+    let last_is_measure_end = strip_trailing(line).0.ends_with('|');
     let ret = first_is_alphanumeric && second_is_measure_sep && last_is_measure_end;
     trace!(line, verdict = ret, "line_is_valid");
     ret
 }
 
-#[derive(Debug)]
+/// Splits a string line like `e|--|` into the name of its string and what comes after the opening
+/// barline.
+fn strict_string_line(line: &str) -> Option<(NoteName, &str)> {
+    let (rem, name) = note_name(line).ok()?;
+    Some((name, rem.strip_prefix('|')?))
+}
+
+/// Splits a string line into the name of its string, if it has one, and what comes after its
+/// opening barline. Besides `e|--|`, this accepts the looser ways some tabs are written:
+/// no names (`|---3---|`), a dash or colon for the barline (`e-|---|`, `e:---|`), a double barline
+/// after a space (`E ||--|`) and quoting (`> e|--|`).
+///
+/// Lines without a name have to contain a `-`, so that bars of chord symbols aren't taken for
+/// strings.
+pub fn lenient_string_line(line: &str) -> Option<(Option<&str>, &str)> {
+    let line = line.trim().trim_start_matches(['>', ' ']);
+    let name_len = match line.chars().next() {
+        Some(c) if c.is_alphanumeric() => {
+            let rest = &line[c.len_utf8()..];
+            let accidental_len = rest.find(|x| !matches!(x, '#' | 'b' | '0'..='9'));
+            c.len_utf8() + accidental_len.unwrap_or(rest.len())
+        }
+        _ => 0,
+    };
+    let (name, rest) = line.split_at(name_len);
+    let body = match name.is_empty() {
+        true => rest.strip_prefix('|').filter(|x| x.contains('-'))?,
+        false => {
+            let rest = rest.trim_start();
+            let barline = rest.strip_prefix("||").or_else(|| rest.strip_prefix('|'));
+            barline.or_else(|| rest.strip_prefix("-|")).or_else(|| rest.strip_prefix(':'))?
+        }
+    };
+    if !strip_trailing(body).0.ends_with('|') {
+        return None;
+    }
+    trace!(line, ?name, body, "lenient_string_line");
+    Some(((!name.is_empty()).then_some(name), body))
+}
+
+/// Parses a line of volta brackets above a part, like `      1.          2.`.
+/// Returns the column and number of each ending.
+pub fn ending_line(line: &str) -> Option<Vec<(usize, u8)>> {
+    let mut endings = vec![];
+    let mut rem = line;
+    loop {
+        let trimmed = rem.trim_start_matches([' ', '[', '|', '_', '-', ']']);
+        if trimmed.is_empty() {
+            break;
+        }
+        let col = char_col_in(line, trimmed);
+        let (after, number) = super::numeric(trimmed).ok()?;
+        rem = after.strip_prefix(['.', ','])?;
+        endings.push((col, number));
+    }
+    (!endings.is_empty()).then_some(endings)
+}
+
+#[derive(Clone, Debug)]
 pub struct Measure {
     pub data_range: RangeInclusive<u32>,
+    /// The measure starts with a `|:` repeat sign
+    pub repeat_start: bool,
+    /// The measure ends with a `:|` repeat sign, and the section is played this many times
+    pub repeat_end: Option<u8>,
+    /// The measure is played only on this pass through the repeated section
+    pub ending: Option<u8>,
+    /// The part of this measure has a rhythm line, so its ticks have real lengths instead of all
+    /// being eighths
+    pub has_rhythm: bool,
 }
 impl Measure {
     pub fn from(range: RangeInclusive<u32>) -> Self {
-        Self { data_range: range }
+        Self {
+            data_range: range,
+            repeat_start: false,
+            repeat_end: None,
+            ending: None,
+            has_rhythm: false,
+        }
     }
 }
 
+/// The order in which measures are played, with repeats and endings unrolled.
+pub fn playback_order(measures: &[Measure]) -> Vec<usize> {
+    let mut order = Vec::with_capacity(measures.len());
+    // the first measure of the repeated section, and how many times we have played it
+    let (mut start, mut pass) = (0, 1);
+    let mut finished_repeat = false;
+    let mut i = 0;
+    while i < measures.len() {
+        let m = &measures[i];
+        if m.ending.is_none() && finished_repeat {
+            (pass, finished_repeat) = (1, false);
+        }
+        if m.repeat_start && i > start {
+            (start, pass) = (i, 1);
+        }
+        if m.ending.is_some_and(|x| x != pass) {
+            i += 1;
+            continue;
+        }
+        order.push(i);
+        if let Some(times) = m.repeat_end {
+            if pass < times {
+                pass += 1;
+                i = start;
+                continue;
+            }
+            (start, finished_repeat) = (i + 1, true);
+        }
+        i += 1;
+    }
+    order
+}
+
+/// The result of parsing a score. On an error, the first one is returned along with what was
+/// parsed up to it, which is boxed to keep the error small.
+pub type ParseOutcome = Result<ParserResult, (BackendError, Box<ParserResult>)>;
+
 #[derive(Debug, Default)]
 pub struct Parser {
     tick_stream: Vec<TabElement>,
     measures: Vec<Measure>,
-    base_notes: Vec<char>,
+    tuning: Vec<u8>,
     /// The line on which the n-th section begins and the index of the first tick in that section.
     /// This provides enough information to restore from where we have read an individual tick.
     offsets: Vec<(u32, u32)>,
+    stray_lines: Vec<u32>,
+    annotations: Vec<Annotation>,
+    rhythm: Vec<Option<Rhythm>>,
+    chords: Vec<(u32, Chord)>,
+    lyrics: Vec<(u32, Syllable)>,
+    sections: Vec<(u32, String)>,
+    trailing: Vec<TrailingAnnotation>,
+    /// A section heading that the next part starts
+    pending_section: Option<String>,
+    spans: Vec<ElementSpan>,
+    /// The number of strings in every part of the score. 0 until the first part is found. The
+    /// parts of an instrument all have the same count, see [ParserResult::string_count].
+    string_count: usize,
+    metadata: ScoreMetadata,
+    /// The label of the instrument whose parts are parsed
+    instrument: Option<String>,
+    settings: ParserSettings,
+    /// The string names of the first part
+    string_names: Vec<NoteName>,
+    /// The line of the last `tuning:` directive and the notes it lists, from the highest string down
+    declared_tuning: Option<(usize, Vec<NoteName>)>,
+    /// The errors that were skipped over when [ParserSettings::recover] is set
+    errors: Vec<BackendError>,
+    diagnostics: Vec<Diagnostic>,
+    has_errors: bool,
+    /// When reparsing after an edit, the previous result to take the unchanged parts from
+    previous: Option<PreviousParse>,
 }
 #[derive(Debug, Default)]
 pub struct ParserResult {
     pub tick_stream: Vec<TabElement>,
     pub measures: Vec<Measure>,
-    pub base_notes: Vec<char>,
+    /// The MIDI note number of each open string, from the first line of a part to the last one.
+    pub tuning: Vec<u8>,
     /// The line on which the n-th section begins and the index of the first tick in that section.
     /// This provides enough information to restore from where we have read an individual tick.
     pub offsets: Vec<(u32, u32)>,
+    /// The empty lines and comments inside of parts, in order. The strings under them are one line
+    /// further down than the first line of the part tells, see [string_line].
+    pub stray_lines: Vec<u32>,
+    /// Palm mutes and similar techniques written on their own line, in the order of their first tick
+    pub annotations: Vec<Annotation>,
+    /// The length of each tick. `None` if the tick takes no time, like the spacing between notes
+    /// under a rhythm line.
+    pub rhythm: Vec<Option<Rhythm>>,
+    /// The chord symbols above the parts, and the tick each of them is over
+    pub chords: Vec<(u32, Chord)>,
+    /// The syllables of the lyric lines under the parts, and the tick each of them is under
+    pub lyrics: Vec<(u32, Syllable)>,
+    /// The names of the sections of the score, like `Verse`, and the index of the measure each of
+    /// them starts with
+    pub sections: Vec<(u32, String)>,
+    /// The text after the closing barlines of the parts, like `(x2)` or `<- let ring`
+    pub trailing: Vec<TrailingAnnotation>,
+    /// Where each element of `tick_stream` is on its line. Empty unless [ParserSettings::spans] is
+    /// set, see [ParserRef::source_span].
+    pub spans: Vec<ElementSpan>,
+    /// The number of strings in every part, and so the length of one tick in `tick_stream`. It is
+    /// the same for the whole result, as the ticks of every part are laid out alike. A score that
+    /// mixes instruments with different counts, like a guitar and a bass, has to label their
+    /// parts, so that [Parser::parse_instruments] gives each of them a result of its own. Without
+    /// labels, the parts that don't match the first one are errors.
+    pub string_count: usize,
+    pub metadata: ScoreMetadata,
+    /// The label of the instrument the parts are for, like `Gtr I`. `None` if the score doesn't
+    /// label its instruments, see [Parser::parse_instruments].
+    pub instrument: Option<String>,
+    /// The guesses the parser made about what the score means, like the tuning of unnamed strings
+    pub diagnostics: Vec<Diagnostic>,
+    /// Whether there were errors in the score. Without [ParserSettings::recover], the parts after
+    /// the first error are missing and the one it is in can be cut short.
+    pub has_errors: bool,
 }
 
 pub struct ParserRef<'a> {
     pub tick_stream: &'a [TabElement],
     pub measures: &'a [Measure],
-    pub base_notes: &'a [char],
+    pub tuning: &'a [u8],
     pub offsets: &'a [(u32, u32)],
+    pub stray_lines: &'a [u32],
+    pub annotations: &'a [Annotation],
+    pub rhythm: &'a [Option<Rhythm>],
+    pub chords: &'a [(u32, Chord)],
+    pub lyrics: &'a [(u32, Syllable)],
+    pub sections: &'a [(u32, String)],
+    pub trailing: &'a [TrailingAnnotation],
+    pub spans: &'a [ElementSpan],
+    pub string_count: usize,
+    pub metadata: &'a ScoreMetadata,
 }
 
 impl Parser {
     pub fn new() -> Self {
         Self::default()
     }
+    pub fn with_settings(settings: ParserSettings) -> Self {
+        Self { settings, ..Default::default() }
+    }
     pub fn clear(&mut self) {
         self.tick_stream.clear();
         self.measures.clear();
-        self.base_notes.clear();
+        self.tuning.clear();
         self.offsets.clear();
+        self.stray_lines.clear();
+        self.annotations.clear();
+        self.rhythm.clear();
+        self.chords.clear();
+        self.lyrics.clear();
+        self.sections.clear();
+        self.trailing.clear();
+        self.pending_section = None;
+        self.spans.clear();
+        self.string_count = 0;
+        self.metadata = ScoreMetadata::default();
+        self.instrument = None;
+        self.string_names.clear();
+        self.declared_tuning = None;
+        self.errors.clear();
+        self.diagnostics.clear();
+        self.has_errors = false;
+        self.previous = None;
     }
     /// Finish the current measure.
     pub fn new_measure(&mut self) {
@@ -78,152 +304,972 @@ impl Parser {
     }
     pub fn parse_inner<L: ParseLines>(&mut self, lines: &L) -> Result<(), BackendError> {
         let mut part_first_line = 0;
-        'outer: loop {
-            // find a part
-            loop {
-                if part_first_line + 5 >= lines.line_count() {
-                    break 'outer;
+        loop {
+            // find a run of string lines
+            while part_first_line < lines.line_count()
+                && !self.is_string_line(lines.get_line(part_first_line))
+            {
+                if let Err(err) =
+                    self.parse_directive(lines.get_line(part_first_line), part_first_line)
+                {
+                    self.recover(err)?;
                 }
-                let (first, last) =
-                    (lines.get_line(part_first_line), lines.get_line(part_first_line + 5));
-                if line_is_valid(first) && line_is_valid(last) {
-                    break;
+                part_first_line += 1;
+            }
+            if part_first_line >= lines.line_count() {
+                break;
+            }
+            let mut run_len = 1;
+            while part_first_line + run_len < lines.line_count()
+                && self.is_string_line(lines.get_line(part_first_line + run_len))
+            {
+                run_len += 1;
+            }
+            let after_stray = self.stray_line_run(lines, part_first_line, run_len);
+            // a run can hold several parts stacked without an empty line between them
+            let part_count = self.check_run(lines, part_first_line, run_len + after_stray)?;
+            for part in 0..part_count {
+                if after_stray != 0 && part + 1 == part_count {
+                    self.push_stray_line(lines, part_first_line + run_len);
                 }
-                part_first_line += 1
+                self.parse_part_or_skip(lines, part_first_line + part * self.string_count)?;
             }
-            let range = part_first_line..=part_first_line + 5;
-            let _part = debug_span!("parsing part", ?range);
-            let _part = _part.enter();
-            self.offsets.push((part_first_line as u32, self.tick_stream.len() as u32));
-            let mut part: [&str; 6] =
-                array::from_fn(|i| lines.get_line(part_first_line + i).trim());
-
-            // The current tick in THIS PART
-            let mut tick = 0;
-            // parse prelude and last char
-            for (line_idx, line) in part.iter_mut().enumerate() {
-                let abs_idx = part_first_line + line_idx;
-                let (rem, string_name) =
-                    string_name(line).map_err(|_| BackendError::invalid_string_name(abs_idx))?;
-                *line = rem;
-                self.base_notes.push(string_name);
-                *line = line
-                    .strip_prefix('|')
-                    .ok_or_else(|| BackendError::invalid_string_name(part_first_line + line_idx))?;
-
-                *line = line
-                    .strip_suffix('|')
-                    .ok_or_else(|| BackendError::no_closing_barline(part_first_line + line_idx))?;
+            part_first_line += run_len + after_stray + (after_stray != 0) as usize;
+        }
+        if let Err(err) = self.resolve_tuning() {
+            self.recover(err)?;
+        }
+        Ok(())
+    }
+    /// Whether this line is a string of a part, which depends on [ParserSettings::lenient].
+    pub(crate) fn is_string_line(&self, line: &str) -> bool {
+        match self.settings.lenient {
+            true => lenient_string_line(line).is_some(),
+            false => line_is_valid(line),
+        }
+    }
+    /// Splits a string line of a part into the name of the string and what comes after the opening
+    /// barline. The name is only missing for lenient parsing.
+    fn split_string_line<'a>(&self, line: &'a str) -> Option<(Option<NoteName>, &'a str)> {
+        if !self.settings.lenient {
+            return strict_string_line(line).map(|(name, rem)| (Some(name), rem));
+        }
+        let (name, rem) = lenient_string_line(line)?;
+        let name = match name.map(note_name) {
+            None => None,
+            Some(Ok(("", name))) => Some(name),
+            Some(_) => return None,
+        };
+        Some((name, rem))
+    }
+    /// Looks for an empty line or a comment that splits the last part of a run of string lines in
+    /// two, which is the case if the strings the run is missing come right after the next line.
+    /// Returns the number of those strings, 0 if there are none.
+    pub(crate) fn stray_line_run<L: ParseLines>(
+        &self, lines: &L, first_line: usize, run_len: usize,
+    ) -> usize {
+        let first_after = first_line + run_len + 1;
+        let mut after = 0;
+        while first_after + after < lines.line_count()
+            && self.is_string_line(lines.get_line(first_after + after))
+        {
+            after += 1;
+        }
+        if after == 0 {
+            return 0;
+        }
+        let string_count =
+            Some(self.string_count).filter(|x| *x != 0).or(self.settings.string_count);
+        let joins = match string_count {
+            Some(n) => !run_len.is_multiple_of(n) && (run_len + after).is_multiple_of(n),
+            // before the first part, a run that starts on the string the first one did is a new
+            // part, like a short line of text after a whole part
+            None => {
+                let name = |idx| self.split_string_line(lines.get_line(idx)).and_then(|x| x.0);
+                let (first, second) = (name(first_line), name(first_after));
+                first.is_some()
+                    && second.is_some()
+                    && first != second
+                    && standard_tuning(run_len + after).is_some()
             }
+        };
+        if joins {
+            after
+        } else {
+            0
+        }
+    }
+    /// Remembers an empty line or a comment inside of the part that is parsed next, see
+    /// [Parser::stray_line_run].
+    pub(crate) fn push_stray_line<L: ParseLines>(&mut self, lines: &L, line_idx: usize) {
+        trace!(line_idx, "found a stray line inside of a part");
+        let kind = match lines.get_line(line_idx).trim().is_empty() {
+            true => DiagnosticKind::EmptyLineInPart,
+            false => DiagnosticKind::CommentInPart,
+        };
+        self.diagnostics.push(Diagnostic::warn(ErrorLocation::LineOnly(line_idx), kind));
+        self.stray_lines.push(line_idx as u32);
+    }
+    /// The line of a string of the part that starts at `part_first_line`, see [string_line].
+    fn string_line(&self, part_first_line: usize, string: usize) -> usize {
+        string_line(&self.stray_lines, part_first_line as u32, string as u32) as usize
+    }
+    /// Checks a run of string lines before its parts are parsed. Returns how many parts it holds,
+    /// which is none for a run that is too short, as that is just text that looks like a string.
+    pub(crate) fn check_run<L: ParseLines>(
+        &mut self, lines: &L, part_first_line: usize, run_len: usize,
+    ) -> Result<usize, BackendError> {
+        if run_len < MIN_STRING_COUNT {
+            trace!(part_first_line, run_len, "too short to be a part, skipping");
+            return Ok(0);
+        }
+        // the first part decides the string count, unless it was declared. Parts with another
+        // count can only be of another instrument, which needs a label of its own
+        if self.string_count == 0 {
+            self.string_count = (self.settings.string_count)
+                .unwrap_or_else(|| self.first_part_len(lines, part_first_line, run_len));
+        }
+        if !run_len.is_multiple_of(self.string_count) {
+            self.recover(BackendError::string_count_mismatch(
+                part_first_line..=part_first_line + run_len - 1,
+                self.string_count,
+                run_len,
+            ))?;
+            return Ok(0);
+        }
+        Ok(run_len / self.string_count)
+    }
+    /// The number of strings of the first part, which is the whole run unless it holds several
+    /// parts stacked on top of each other. Those are found by their string names repeating, as a
+    /// part can name the same note twice, like the two `D`s of drop D.
+    fn first_part_len<L: ParseLines>(&self, lines: &L, first_line: usize, run_len: usize) -> usize {
+        let names: Option<Vec<_>> = (first_line..first_line + run_len)
+            .map(|idx| self.split_string_line(lines.get_line(idx)).and_then(|x| x.0))
+            .collect();
+        let Some(names) = names else { return run_len };
+        (MIN_STRING_COUNT..run_len)
+            .filter(|len| run_len.is_multiple_of(*len))
+            .find(|len| names.iter().zip(names.iter().skip(*len)).all(|(a, b)| a == b))
+            .unwrap_or(run_len)
+    }
+    /// Parses a part, or forgets it if it has an error outside of its measures and the settings
+    /// ask for recovery.
+    pub(crate) fn parse_part_or_skip<L: ParseLines>(
+        &mut self, lines: &L, part_first_line: usize,
+    ) -> Result<(), BackendError> {
+        let last_string = self.string_count - 1;
+        // the first part is always parsed, as its string names are not in the result. The lines of
+        // a part with a stray line aren't where the previous result expects them.
+        let reusable = (self.previous.as_ref())
+            .filter(|_| !self.string_names.is_empty())
+            .filter(|_| {
+                self.string_line(part_first_line, last_string) == part_first_line + last_string
+            })
+            .and_then(|x| x.reusable_part(lines, part_first_line, self.string_count));
+        if let Some(part_idx) = reusable {
+            trace!(part_first_line, part_idx, "reusing part of the previous result");
+            self.copy_part(part_idx, part_first_line);
+            return Ok(());
+        }
+        if let Err(err) = self.parse_part(lines, part_first_line) {
+            self.recover(err)?;
+            self.skip_part();
+        }
+        Ok(())
+    }
+    /// Appends a part of the previous result, which is now at `part_first_line`.
+    fn copy_part(&mut self, part_idx: usize, part_first_line: usize) {
+        let previous = &self.previous.as_ref().unwrap().result;
+        let n = self.string_count as u32;
+        let start = previous.offsets[part_idx].1;
+        let end =
+            (previous.offsets.get(part_idx + 1)).map_or(previous.tick_stream.len() as u32, |x| x.1);
+        let new_start = self.tick_stream.len() as u32;
+        // ticks after the part are moved by the difference of the starts, which can be negative
+        let shift = |x: u32| x + new_start - start;
+        let tick_shift = |x: u32| x + new_start / n - start / n;
+        let in_part = |x: u32| (start..end).contains(&x);
+        let in_part_ticks = |x: u32| (start / n..end / n).contains(&x);
 
-            let mut tick_cnt_est = part[0].len();
-
-            while tick < tick_cnt_est {
-                let s = span!(Level::TRACE, "parsing tick", tick);
-                let _s = s.enter();
-                let mut is_multi_on = [false; 6];
-                for s in 0..6 {
-                    trace!(part = part[s], "remaining on string {s}:");
-                    if s == 0 && part[s].starts_with("|") {
-                        trace!("encountered measure separator");
-                        self.new_measure();
-                        part.iter_mut().for_each(|string| *string = &string[1..]); // TODO: maybe debugassert here that it is indeed a measure separator
-                        tick_cnt_est -= 1;
-                        trace!(part = part[s], "remaining on string {s}: after fixup");
-                    }
+        self.offsets.push((part_first_line as u32, new_start));
+        let first_measure = self.measures.len();
+        self.tick_stream.extend_from_slice(&previous.tick_stream[start as usize..end as usize]);
+        let measures = previous.measures.iter().filter(|x| in_part(*x.data_range.start()));
+        self.measures.extend(measures.map(|x| Measure {
+            data_range: shift(*x.data_range.start())..=shift(*x.data_range.end()),
+            ..x.clone()
+        }));
+        let annotations = previous.annotations.iter().filter(|x| in_part_ticks(*x.ticks.start()));
+        self.annotations.extend(annotations.map(|x| Annotation {
+            kind: x.kind,
+            ticks: tick_shift(*x.ticks.start())..=tick_shift(*x.ticks.end()),
+        }));
+        self.rhythm.extend_from_slice(&previous.rhythm[(start / n) as usize..(end / n) as usize]);
+        if let Some(spans) = previous.spans.get(start as usize..end as usize) {
+            self.spans.extend_from_slice(spans);
+        }
+        let chords = previous.chords.iter().filter(|x| in_part_ticks(x.0));
+        self.chords.extend(chords.map(|(tick, chord)| (tick_shift(*tick), chord.clone())));
+        let lyrics = previous.lyrics.iter().filter(|x| in_part_ticks(x.0));
+        self.lyrics.extend(lyrics.map(|(tick, syllable)| (tick_shift(*tick), syllable.clone())));
+        // the diagnostics and trailing text of a part are on its lines
+        let old_line = previous.offsets[part_idx].0 as usize;
+        let old_lines = old_line..old_line + n as usize;
+        let old_measure = previous.measures.partition_point(|x| *x.data_range.start() < start);
+        let trailing = previous.trailing.iter().filter(|x| old_lines.contains(&(x.line as usize)));
+        self.trailing.extend(trailing.map(|x| TrailingAnnotation {
+            line: x.line - old_line as u32 + part_first_line as u32,
+            measure: x.measure - old_measure as u32 + first_measure as u32,
+            trailing: x.trailing.clone(),
+        }));
+        let diagnostics = (previous.diagnostics.iter())
+            .filter(|x| x.location.get_line_idx().is_some_and(|x| old_lines.contains(&x)));
+        self.diagnostics.extend(diagnostics.map(|x| Diagnostic {
+            location: x.location.map_line(|x| x - old_line + part_first_line),
+            ..x.clone()
+        }));
+        // the heading above the part could have been edited, so it is taken from the new lines
+        self.apply_section(first_measure);
+    }
+    /// Marks the measure that was just finished as the start of a repeat if needed.
+    fn finish_measure_start(&mut self, repeat_start: &mut bool) {
+        self.measures.last_mut().unwrap().repeat_start = *repeat_start;
+        *repeat_start = false;
+    }
+    /// Assigns the endings of a volta line above a part to the measures under them.
+    fn apply_endings(&mut self, line: &str, measure_cols: &[usize], first_measure: usize) {
+        let Some(endings) = ending_line(line) else { return };
+        for (col, number) in endings {
+            let in_part = measure_cols.partition_point(|x| *x <= col).saturating_sub(1);
+            // the measure can be missing if it was skipped because of an error
+            if let Some(measure) = self.measures.get_mut(first_measure + in_part) {
+                measure.ending = Some(number);
+            }
+        }
+    }
+    /// Finds the annotation lines directly above and below a part, and stores their tick ranges.
+    /// Lines below the part that are directly followed by another part belong to that part instead.
+    fn apply_annotations<L: ParseLines>(
+        &mut self, lines: &L, above: Range<usize>, part_first_line: usize, tick_cols: &[usize],
+    ) {
+        if tick_cols.is_empty() {
+            return;
+        }
+        let is_annotation = |idx: usize| annotation_line(lines.get_line(idx)).is_some();
+        let part_end = self.string_line(part_first_line, self.string_count - 1) + 1;
+        let mut end = part_end;
+        while end < lines.line_count() && is_annotation(end) {
+            end += 1;
+        }
+        if end < lines.line_count() && self.is_string_line(lines.get_line(end)) {
+            end = part_end;
+        }
+        let first_tick = self.offsets.last().unwrap().1 / self.string_count as u32;
+        let tick_at = |col: usize| first_tick + tick_at_col(tick_cols, col) as u32;
+        let start = self.annotations.len();
+        for line_idx in above.chain(part_end..end) {
+            for (kind, cols) in annotation_line(lines.get_line(line_idx)).unwrap_or_default() {
+                let ticks = tick_at(*cols.start())..=tick_at(*cols.end());
+                trace!(?kind, ?ticks, line_idx, "found annotation");
+                self.annotations.push(Annotation { kind, ticks });
+            }
+        }
+        self.annotations[start..].sort_by_key(|x| *x.ticks.start());
+    }
+    /// Gives every tick of the part that was just parsed a length, from the rhythm line above it.
+    /// Notes without a rhythm of their own continue the previous one, and the rests between them
+    /// are only spacing, so they take no time.
+    fn apply_rhythm<L: ParseLines>(
+        &mut self, lines: &L, above: Range<usize>, tick_cols: &[usize], first_measure: usize,
+    ) {
+        let rhythms = above.clone().find_map(|x| rhythm_line(lines.get_line(x)));
+        let Some(rhythms) = rhythms.filter(|_| !tick_cols.is_empty()) else {
+            self.rhythm.extend(std::iter::repeat_n(Some(Rhythm::EIGHTH), tick_cols.len()));
+            return;
+        };
+        let mut written = vec![None; tick_cols.len()];
+        for (col, rhythm) in rhythms {
+            written[tick_at_col(tick_cols, col)] = Some(rhythm);
+        }
+        let n = self.string_count;
+        let part_start = self.offsets.last().unwrap().1 as usize;
+        let mut last = Rhythm::EIGHTH;
+        for (tick, written) in written.into_iter().enumerate() {
+            let elems = &self.tick_stream[part_start + tick * n..][..n];
+            let rhythm = match written {
+                Some(rhythm) => Some(rhythm),
+                None if elems.iter().any(TabElement::is_note) => Some(last),
+                None => None,
+            };
+            last = rhythm.unwrap_or(last);
+            self.rhythm.push(rhythm);
+        }
+        self.measures[first_measure..].iter_mut().for_each(|x| x.has_rhythm = true);
+    }
+    /// Places the text after the closing barlines of the part that was just parsed next to its
+    /// last measure. The text is dropped if none of the measures are left.
+    fn apply_trailing(&mut self, trailing: Vec<(u32, Trailing)>, first_measure: usize) {
+        if self.measures.len() <= first_measure {
+            return;
+        }
+        let measure = self.measures.len() as u32 - 1;
+        let trailing = trailing.into_iter().map(|(line, trailing)| TrailingAnnotation {
+            line,
+            measure,
+            trailing,
+        });
+        self.trailing.extend(trailing);
+    }
+    /// Starts the section of the last heading at `first_measure`, the first measure of the part that
+    /// was just parsed. The heading waits for the next part if none of the measures are left.
+    fn apply_section(&mut self, first_measure: usize) {
+        if first_measure < self.measures.len() {
+            if let Some(name) = self.pending_section.take() {
+                self.sections.push((first_measure as u32, name));
+            }
+        }
+    }
+    /// Places the chord symbols of the line above the part that was just parsed on its ticks.
+    fn apply_chords<L: ParseLines>(&mut self, lines: &L, above: Range<usize>, tick_cols: &[usize]) {
+        if tick_cols.is_empty() {
+            return;
+        }
+        // a line like `E   E` is a rhythm, not chords
+        let mut chord_lines = above.map(|x| lines.get_line(x)).filter(|x| rhythm_line(x).is_none());
+        let Some(chords) = chord_lines.find_map(chord_line) else { return };
+        let first_tick = self.offsets.last().unwrap().1 / self.string_count as u32;
+        for (col, chord) in chords {
+            let tick = first_tick + tick_at_col(tick_cols, col) as u32;
+            trace!(%chord, tick, "found chord");
+            self.chords.push((tick, chord));
+        }
+    }
+    /// Places the syllables of the lyric line under the part that was just parsed on its ticks.
+    /// The lyrics can be below the annotations of the part, and anything that means something
+    /// else, like a string line or a directive, is not a lyric line.
+    fn apply_lyrics<L: ParseLines>(
+        &mut self, lines: &L, part_first_line: usize, tick_cols: &[usize],
+    ) {
+        let mut line_idx = self.string_line(part_first_line, self.string_count - 1) + 1;
+        while line_idx < lines.line_count() && annotation_line(lines.get_line(line_idx)).is_some() {
+            line_idx += 1;
+        }
+        if tick_cols.is_empty() || line_idx >= lines.line_count() {
+            return;
+        }
+        let line = lines.get_line(line_idx);
+        let is_directive =
+            line.split_once(':').is_some_and(|(key, _)| MetadataKey::from_key(key).is_some());
+        if line.trim().is_empty()
+            || line.trim_start().starts_with("//")
+            || self.is_string_line(line)
+            || instrument_label(line).is_some()
+            || section_label(line).is_some()
+            || is_directive
+            || is_line_above(line)
+        {
+            return;
+        }
+        let first_tick = self.offsets.last().unwrap().1 / self.string_count as u32;
+        for (col, syllable) in lyric_line(line) {
+            let tick = first_tick + tick_at_col(tick_cols, col) as u32;
+            trace!(?syllable, tick, "found syllable");
+            self.lyrics.push((tick, syllable));
+        }
+    }
+    /// Handles `key: value` lines outside of parts, which describe the score, like `tempo: 90` or
+    /// `tuning: D A D G B E`, and section headings like `[Verse]`, which name the next part.
+    pub(crate) fn parse_directive(
+        &mut self, line: &str, line_idx: usize,
+    ) -> Result<(), BackendError> {
+        if let Some(name) = section_label(line) {
+            trace!(name, line_idx, "found section");
+            self.pending_section = Some(name.to_string());
+            return Ok(());
+        }
+        let Some((key, value)) = line.split_once(':') else { return Ok(()) };
+        let Some(key) = MetadataKey::from_key(key) else { return Ok(()) };
+        self.metadata
+            .set(&key, value)
+            .ok_or_else(|| BackendError::invalid_metadata(line_idx, key.name()))?;
+        // "standard" means that the string names can be trusted
+        let standard = value.trim().get(..8).is_some_and(|x| x.eq_ignore_ascii_case("standard"));
+        if key == MetadataKey::Tuning && !standard {
+            let mut names =
+                tuning_directive(value).ok_or_else(|| BackendError::invalid_tuning(line_idx))?;
+            // the directive lists the strings from the lowest, but parts start with the highest
+            names.reverse();
+            self.declared_tuning = Some((line_idx, names));
+        }
+        Ok(())
+    }
+    pub(crate) fn resolve_tuning(&mut self) -> Result<(), BackendError> {
+        let (line_idx, names) = match self.declared_tuning.take() {
+            Some((line_idx, names)) => {
+                if self.string_count != 0 && names.len() != self.string_count {
+                    return Err(BackendError::tuning_mismatch(
+                        line_idx,
+                        self.string_count,
+                        names.len(),
+                    ));
+                }
+                (line_idx, names)
+            }
+            None => {
+                (self.offsets.first().map(|x| x.0 as usize).unwrap_or(0), self.string_names.clone())
+            }
+        };
+        self.tuning =
+            resolve_tuning(&names).ok_or_else(|| BackendError::invalid_tuning(line_idx))?;
+        Ok(())
+    }
+    fn parse_part<L: ParseLines>(
+        &mut self, lines: &L, part_first_line: usize,
+    ) -> Result<(), BackendError> {
+        let string_count = self.string_count;
+        let range = part_first_line..=self.string_line(part_first_line, string_count - 1);
+        let _part = debug_span!("parsing part", ?range);
+        let _part = _part.enter();
+        self.offsets.push((part_first_line as u32, self.tick_stream.len() as u32));
+        // the whole lines, to tell where the elements are on them
+        let source: Vec<&str> = (0..string_count)
+            .map(|i| lines.get_line(self.string_line(part_first_line, i)))
+            .collect();
+        let mut part: Vec<&str> = source.iter().map(|x| x.trim()).collect();
 
-                    let len_before = part[s].len();
-                    let (res, te) = self.parse_tab_element(&part, s, part_first_line)?;
+        // The current tick in THIS PART
+        let mut tick = 0;
+        let mut repeat_count = None;
+        // the text after the closing barline of each line
+        let mut trailing = vec![];
+        let mut string_names = Vec::with_capacity(string_count);
+        // parse prelude and last char
+        for (line_idx, line) in part.iter_mut().enumerate() {
+            let abs_idx = self.string_line(part_first_line, line_idx);
+            let (string_name, rem) =
+                self.split_string_line(line).ok_or(BackendError::invalid_string_name(abs_idx))?;
+            if strict_string_line(line).is_none_or(|x| x.1 != rem) {
+                let location = ErrorLocation::LineAndChar(
+                    abs_idx as u32,
+                    char_col_in(lines.get_line(abs_idx), rem) as u32 - 1,
+                );
+                self.diagnostics
+                    .push(Diagnostic::info(location, DiagnosticKind::LenientStringLine));
+            }
+            *line = rem;
+            string_names.push(string_name);
 
-                    let tab_element_len = len_before - res.len();
-                    is_multi_on[s] = tab_element_len > 1;
-                    part[s] = res;
-                    self.tick_stream.push(te);
+            let (rem, text) = strip_trailing(line);
+            if let Some(text) = text {
+                if let Trailing::RepeatCount(count) = text {
+                    repeat_count = repeat_count.max(Some(count));
                 }
-                if is_multi_on.iter().any(|x| *x) {
-                    let _ms = span!(Level::DEBUG, "marked as multichar, running fixup", tick);
-                    let _ms = _ms.enter();
-                    tick_cnt_est -= 1;
-                    for s in 0..6 {
-                        if is_multi_on[s] {
-                            trace!("multi on {s}, skipping");
-                            continue;
-                        };
-                        let elem_idx = self.tick_stream.len() - (6 - s);
-                        let elem = &self.tick_stream[elem_idx];
-                        let idx32 = elem_idx as u32;
-                        let _s = debug_span!("fixing up string", string = s, ?elem);
-                        let _s = _s.enter();
-                        if let TabElement::Rest = elem {
-                            let _s2 = trace_span!("this is a rest, trying to parse next element");
-                            let _s2 = _s2.enter();
-                            let len_before = part[s].len();
-                            let (rem, next_elem) =
-                                self.parse_tab_element(&part, s, part_first_line)?;
-                            if len_before - rem.len() > 1 {
-                                let (m_line, m_char) = self.source_location_from_stream(idx32);
-                                // just for a nicer error, show another multi line too
-                                let other =
-                                    is_multi_on.iter().enumerate().find(|a| *a.1).unwrap().0
-                                        + part_first_line;
-                                return Err(BackendError::both_slots_multichar(
-                                    m_line,
-                                    m_char,
-                                    other as u32,
-                                ));
-                            }
-                            trace!(replacement = ?next_elem, "replaced a rest");
-                            let len = self.tick_stream.len(); // to make the borrow checker happy about borrowing &mut and &
-                            self.tick_stream[len - (6 - s)] = next_elem;
-                            part[s] = rem;
-                        } else {
-                            let _q = trace_span!("this is not a Rest, checking the next element");
-                            let _q = _q.enter();
-                            let Some(rem) = part[s].strip_prefix('-') else {
-                                let (line, char) = self.source_location_from_stream(idx32);
-                                return Err(BackendError::multi_both_slots_filled(line, char));
-                            };
-                            part[s] = rem;
-                        }
-                    }
+                trailing.push((abs_idx as u32, text));
+            }
+            *line =
+                rem.strip_suffix('|').ok_or_else(|| BackendError::no_closing_barline(abs_idx))?;
+        }
+        // a part names either all of its strings or none of them
+        let unnamed = string_names.iter().all(Option::is_none);
+        let string_names: Vec<NoteName> = match unnamed {
+            true => standard_tuning(string_count)
+                .ok_or(BackendError::invalid_string_name(part_first_line))?,
+            false => (string_names.into_iter().enumerate())
+                .map(|(i, x)| {
+                    x.ok_or(BackendError::invalid_string_name(self.string_line(part_first_line, i)))
+                })
+                .collect::<Result<_, _>>()?,
+        };
+        // the names of the first part are the ones used for the tuning
+        if self.string_names.is_empty() {
+            if unnamed && self.declared_tuning.is_none() {
+                let kind = DiagnosticKind::AssumedStandardTuning { string_count };
+                self.diagnostics
+                    .push(Diagnostic::warn(ErrorLocation::LineOnly(part_first_line), kind));
+            }
+            self.string_names = string_names;
+        }
+        let first_measure = self.measures.len();
+        let mut repeat_start = strip_repeat_column(&mut part, Side::Start);
+        let mut seen_repeat_start = repeat_start;
+        let repeat_end = strip_repeat_column(&mut part, Side::End);
+        // where each measure of this part starts on the first line, to place endings
+        let line0 = lines.get_line(part_first_line);
+        let mut measure_cols = vec![char_col_in(line0, part[0])];
+        // where each tick of this part starts on the first line, to place annotations
+        let mut tick_cols = vec![];
+
+        // how many characters the element of the current tick takes up on each string
+        let mut widths = vec![0; string_count];
+        let mut skipped_last_measure = false;
+        // the index of the current measure among the ones of this part, for errors
+        let mut measure_in_part = 0;
+
+        loop {
+            let s = span!(Level::TRACE, "parsing tick", tick);
+            let _s = s.enter();
+            let mut ends_repeat = false;
+            if part.iter().any(|x| x.starts_with(":|"))
+                && strip_repeat_column(&mut part, Side::Start)
+            {
+                trace!("encountered end repeat");
+                ends_repeat = true;
+            }
+            let aligned = self.check_barlines(&part, part_first_line, measure_in_part);
+            if aligned.is_ok() && part[0].is_empty() {
+                break;
+            }
+            if aligned.is_ok() && part[0].starts_with("|") {
+                trace!("encountered measure separator");
+                self.new_measure();
+                self.finish_measure_start(&mut repeat_start);
+                if ends_repeat {
+                    self.measures.last_mut().unwrap().repeat_end = Some(2);
+                }
+                part.iter_mut().for_each(|string| *string = &string[1..]);
+                measure_in_part += 1;
+                measure_cols.push(char_col_in(line0, part[0]));
+                if strip_repeat_column(&mut part, Side::Start) {
+                    trace!("encountered start repeat");
+                    (repeat_start, seen_repeat_start) = (true, true);
+                }
+                trace!(part = part[0], "remaining on string 0: after fixup");
+            }
+            let parsed = aligned.and_then(|_| {
+                self.parse_tick(&mut part, &source, &mut widths, &mut tick_cols, part_first_line)
+            });
+            if let Err(err) = parsed {
+                self.recover(err)?;
+                self.skip_measure(&mut part);
+                measure_in_part += 1;
+                // the first string is over, so what is left on the others is dropped too
+                if part[0].is_empty() {
+                    part.fill("");
+                }
+                let part_start = self.offsets.last().unwrap().1 as usize;
+                tick_cols.truncate((self.tick_stream.len() - part_start) / string_count);
+                // the skipped measure is gone, so the next one starts where it did
+                measure_cols.pop();
+                if !part[0].is_empty() {
+                    measure_cols.push(char_col_in(line0, part[0]));
                 }
-                trace!(tick, data = dump_tracks(&self.as_ref()), "data after parsing tick");
-                trace!(source = dump_source(&part), "source state after parsing tick");
-                tick += 1;
+                skipped_last_measure = true;
+                continue;
             }
-            self.new_measure();
+            skipped_last_measure = false;
+            trace!(tick, data = dump_tracks(&self.as_ref()), "data after parsing tick");
+            trace!(source = dump_source(&part), "source state after parsing tick");
+            tick += 1;
+        }
+        // the last measure had an error, and there is nothing after it to finish
+        if skipped_last_measure {
+            self.apply_trailing(trailing, first_measure);
+            self.finish_part(lines, part_first_line, &measure_cols, &tick_cols, first_measure);
+            return Ok(());
+        }
+        self.new_measure();
+        self.finish_measure_start(&mut repeat_start);
+        if repeat_end || repeat_count.is_some() {
+            self.measures.last_mut().unwrap().repeat_end = Some(repeat_count.unwrap_or(2));
+        }
+        // a bare `|x4` repeats the whole line
+        if !repeat_end && repeat_count.is_some() && !seen_repeat_start {
+            self.measures[first_measure].repeat_start = true;
+        }
+        self.apply_trailing(trailing, first_measure);
+        self.finish_part(lines, part_first_line, &measure_cols, &tick_cols, first_measure);
 
-            // finished parsing part
-            trace!(part = dump_tracks(&self.as_ref()), "Finished part");
+        // finished parsing part
+        trace!(part = dump_tracks(&self.as_ref()), "Finished part");
+        Ok(())
+    }
+    /// Applies the lines above and below the part that was just parsed to it.
+    fn finish_part<L: ParseLines>(
+        &mut self, lines: &L, part_first_line: usize, measure_cols: &[usize], tick_cols: &[usize],
+        first_measure: usize,
+    ) {
+        self.apply_section(first_measure);
+        let above = lines_above(lines, part_first_line);
+        if let Some(endings) = above.clone().find(|x| ending_line(lines.get_line(*x)).is_some()) {
+            self.apply_endings(lines.get_line(endings), measure_cols, first_measure);
+        }
+        self.apply_annotations(lines, above.clone(), part_first_line, tick_cols);
+        self.apply_rhythm(lines, above.clone(), tick_cols, first_measure);
+        self.apply_chords(lines, above, tick_cols);
+        self.apply_lyrics(lines, part_first_line, tick_cols);
+    }
+    /// Parses one element on every string, and joins them into a tick as wide as the widest one.
+    ///
+    /// When the elements have different widths, more elements are read on the shorter strings
+    /// until all of them end on the same column. Usually every string has at most one element that
+    /// isn't a rest in that space, and they become a single tick. Otherwise, like for `5h7` under
+    /// `12h14`, there is a tick for every column in that space where an element starts.
+    ///
+    /// The column of every tick that was added is pushed to `tick_cols`. Widths are counted in
+    /// characters.
+    fn parse_tick(
+        &mut self, part: &mut [&str], source: &[&str], widths: &mut [usize],
+        tick_cols: &mut Vec<usize>, part_first_line: usize,
+    ) -> Result<(), BackendError> {
+        let string_count = self.string_count;
+        let tick_col = char_col_in(source[0], part[0]);
+        for s in 0..string_count {
+            trace!(part = part[s], "remaining on string {s}:");
+            let (res, te) = self.parse_tab_element(part, source, s, part_first_line)?;
 
-            part_first_line += 6;
+            widths[s] = char_width(part[s], res);
+            if self.settings.spans {
+                let (start, end) = (char_col_in(source[s], part[s]), char_col_in(source[s], res));
+                self.spans.push(ElementSpan { start: start as u32, end: end as u32 });
+            }
+            part[s] = res;
+            self.tick_stream.push(te);
+        }
+        let mut tick_width = *widths.iter().max().unwrap();
+        if widths.iter().all(|x| *x == tick_width) {
+            tick_cols.push(tick_col);
+            return Ok(());
+        }
+        let _ms = span!(Level::DEBUG, "marked as multichar, running fixup");
+        let _ms = _ms.enter();
+        let first = self.tick_stream.len() - string_count;
+        // the elements after the first one of each string, with their string and column in the tick
+        let mut extra = vec![];
+        loop {
+            for s in 0..string_count {
+                while widths[s] < tick_width {
+                    let (rem, elem) = self.parse_tab_element(part, source, s, part_first_line)?;
+                    trace!(string = s, ?elem, col = widths[s], "element inside a multichar tick");
+                    extra.push((s, widths[s], elem));
+                    widths[s] += char_width(part[s], rem);
+                    part[s] = rem;
+                }
+            }
+            // an element on one string can reach past the end of the others
+            let width = *widths.iter().max().unwrap();
+            if width == tick_width {
+                break;
+            }
+            tick_width = width;
+        }
+        let mut notes = vec![0; string_count];
+        for (s, elem) in self.tick_stream[first..].iter().enumerate() {
+            notes[s] += !matches!(elem, TabElement::Rest) as usize;
+        }
+        for (s, _, elem) in &extra {
+            notes[*s] += !matches!(elem, TabElement::Rest) as usize;
+        }
+        if notes.iter().all(|x| *x <= 1) {
+            // one tick, with the element of each string wherever it is aligned
+            for (s, _, elem) in extra.into_iter().filter(|x| !matches!(x.2, TabElement::Rest)) {
+                trace!(replacement = ?elem, "replaced a rest");
+                self.tick_stream[first + s] = elem;
+            }
+            if self.settings.spans {
+                for s in 0..string_count {
+                    self.spans[first + s].end = char_col_in(source[s], part[s]) as u32;
+                }
+            }
+            tick_cols.push(tick_col);
+            return Ok(());
+        }
+        // a tick for every column where an element starts. `starts` is where the tick starts on
+        // each string, for the spans.
+        let starts: Vec<u32> =
+            self.spans.get(first..).unwrap_or_default().iter().map(|x| x.start).collect();
+        let firsts: Vec<TabElement> = self.tick_stream.drain(first..).collect();
+        self.spans.truncate(first);
+        let mut cols: Vec<usize> = extra.iter().map(|x| x.1).collect();
+        cols.push(0);
+        cols.sort_unstable();
+        cols.dedup();
+        trace!(?cols, "split multichar tick");
+        for (i, col) in cols.iter().enumerate() {
+            let next = cols.get(i + 1).copied().unwrap_or(tick_width);
+            for (s, first_elem) in firsts.iter().enumerate() {
+                let elem = match col {
+                    0 => Some(first_elem),
+                    _ => extra.iter().find(|x| x.0 == s && x.1 == *col).map(|x| &x.2),
+                };
+                self.tick_stream.push(elem.cloned().unwrap_or(TabElement::Rest));
+                if let Some(start) = starts.get(s) {
+                    let span = ElementSpan { start: start + *col as u32, end: start + next as u32 };
+                    self.spans.push(span);
+                }
+            }
+            tick_cols.push(tick_col + *col);
+        }
+        Ok(())
+    }
+    /// Checks that every string of a part has a barline, or its end, where the others do. The
+    /// strings that agree with the most others are taken to be right, and the first one that
+    /// doesn't is reported.
+    fn check_barlines(
+        &self, part: &[&str], part_first_line: usize, measure_in_part: usize,
+    ) -> Result<(), BackendError> {
+        #[derive(PartialEq, Clone, Copy)]
+        enum Position {
+            Inside,
+            Barline,
+            End,
+        }
+        let position = |x: &str| match x.chars().next() {
+            None => Position::End,
+            Some('|') => Position::Barline,
+            Some(_) => Position::Inside,
+        };
+        let first = position(part[0]);
+        if part.iter().all(|x| position(x) == first) {
+            return Ok(());
         }
+        let count = |p| part.iter().filter(|x| position(x) == p).count();
+        let expected = [Position::Inside, Position::Barline, Position::End]
+            .into_iter()
+            .max_by_key(|x| (count(*x), *x == first))
+            .unwrap();
+        let string = part.iter().position(|x| position(x) != expected).unwrap();
+        let lines = part_first_line..=self.string_line(part_first_line, part.len() - 1);
+        let line = self.string_line(part_first_line, string);
+        Err(BackendError::misaligned_barline(lines, line, measure_in_part, string))
+    }
+    /// Remembers the error and keeps going if the settings ask for recovery, or returns it.
+    pub(crate) fn recover(&mut self, err: BackendError) -> Result<(), BackendError> {
+        self.has_errors = true;
+        if !self.settings.recover {
+            return Err(err);
+        }
+        debug!(?err, "recovering from error");
+        self.errors.push(err);
         Ok(())
     }
-    pub fn parse<L: ParseLines>(lines: &L) -> Result<ParserResult, (BackendError, ParserResult)> {
-        let mut parser = Self::new();
-        match parser.parse_inner(lines) {
+    /// Forgets the part that had an error before any of its ticks were parsed.
+    fn skip_part(&mut self) {
+        self.offsets.pop();
+    }
+    /// Drops the measure that had an error, and moves every string past its closing barline. If
+    /// there is no barline left, the rest of the part is dropped.
+    fn skip_measure(&mut self, part: &mut [&str]) {
+        let measure_start = self.measures.last().map(|x| x.data_range.end() + 1).unwrap_or(0);
+        self.tick_stream.truncate(measure_start as usize);
+        self.spans.truncate(measure_start as usize);
+        for string in part.iter_mut() {
+            let next = string.find('|').map_or(string.len(), |x| x + 1);
+            *string = &string[next..];
+        }
+    }
+    pub fn parse<L: ParseLines>(lines: &L) -> ParseOutcome {
+        Self::parse_with(lines, ParserSettings::default())
+    }
+    pub fn parse_with<L: ParseLines>(lines: &L, settings: ParserSettings) -> ParseOutcome {
+        let (parsed, mut errors) = Self::parse_all(lines, settings);
+        match errors.is_empty() {
+            true => Ok(parsed),
+            false => Err((errors.swap_remove(0), Box::new(parsed))),
+        }
+    }
+    /// Parses a score again after an edit. Only the parts that the edit could have changed are
+    /// parsed, the rest are taken from `previous`, which has to be the result of parsing the
+    /// score before the edit with the same settings. `lines` is the score after the edit.
+    ///
+    /// The result is the same as that of [Parser::parse_with] on `lines`. When recovering from
+    /// errors, or when `previous` had errors, everything is parsed again.
+    pub fn reparse<L: ParseLines>(
+        previous: ParserResult, lines: &L, edit: &TextEdit, settings: ParserSettings,
+    ) -> ParseOutcome {
+        let mut parser = Self::with_settings(settings);
+        // the parts of a score with errors can be incomplete
+        if !parser.settings.recover && !previous.has_errors {
+            parser.previous = Some(PreviousParse::new(previous, edit));
+        }
+        let result = parser.parse_inner(lines);
+        parser.previous = None;
+        match result {
             Ok(_) => Ok(parser.into_result()),
-            Err(y) => Err((y, parser.into_result())),
+            Err(y) => Err((y, Box::new(parser.into_result()))),
+        }
+    }
+    /// Like [Parser::parse_with], but returns every error found. There can only be more than one
+    /// when [ParserSettings::recover] is set, in which case the result holds everything that
+    /// could be parsed around the errors.
+    pub fn parse_all<L: ParseLines>(
+        lines: &L, settings: ParserSettings,
+    ) -> (ParserResult, Vec<BackendError>) {
+        let mut parser = Self::with_settings(settings);
+        if let Err(err) = parser.parse_inner(lines) {
+            parser.errors.push(err);
+        }
+        let errors = std::mem::take(&mut parser.errors);
+        (parser.into_result(), errors)
+    }
+    /// Like [Parser::parse_all], but for scores of several instruments, like a band tab with
+    /// `Gtr I`, `Gtr II` and `Bass` systems stacked on each other. The parts after a label are of
+    /// that instrument until the next label, see [InstrumentLines]. Returns a result for every
+    /// instrument in the order they first appear, each with its own string count and tuning, or a
+    /// single one if the score has no labels.
+    ///
+    /// Directives outside of the parts apply to every instrument. The errors of a line that every
+    /// instrument reads are only returned once.
+    pub fn parse_instruments<L: ParseLines>(
+        lines: &L, settings: ParserSettings,
+    ) -> (Vec<ParserResult>, Vec<BackendError>) {
+        let recover = settings.recover;
+        let probe = Self::with_settings(settings.clone());
+        let instruments = InstrumentLines::find(lines, |x| probe.is_string_line(x));
+        if instruments.names.len() <= 1 {
+            let (mut parsed, errors) = Self::parse_all(lines, settings);
+            parsed.instrument = instruments.names.into_iter().next().flatten();
+            return (vec![parsed], errors);
+        }
+        let (mut results, mut errors) = (vec![], vec![]);
+        for (idx, name) in instruments.names.iter().enumerate() {
+            let mut parser = Self::with_settings(settings.clone());
+            parser.instrument = name.clone();
+            if let Err(err) = parser.parse_inner(&instruments.view(lines, idx)) {
+                parser.errors.push(err);
+            }
+            for err in std::mem::take(&mut parser.errors) {
+                if !errors.iter().any(|x: &BackendError| x.main_location == err.main_location) {
+                    errors.push(err);
+                }
+            }
+            results.push(parser.into_result());
+            if !errors.is_empty() && !recover {
+                break;
+            }
+        }
+        (results, errors)
+    }
+    /// Moves out the parts parsed so far, so that the next ones start from an empty tick stream.
+    /// What belongs to the whole score, like the string count and the tuning, is kept.
+    pub(crate) fn take_result(&mut self) -> ParserResult {
+        use std::mem::take;
+        ParserResult {
+            tick_stream: take(&mut self.tick_stream),
+            measures: take(&mut self.measures),
+            tuning: self.tuning.clone(),
+            offsets: take(&mut self.offsets),
+            stray_lines: take(&mut self.stray_lines),
+            annotations: take(&mut self.annotations),
+            rhythm: take(&mut self.rhythm),
+            chords: take(&mut self.chords),
+            lyrics: take(&mut self.lyrics),
+            sections: take(&mut self.sections),
+            trailing: take(&mut self.trailing),
+            spans: take(&mut self.spans),
+            string_count: self.string_count,
+            metadata: self.metadata.clone(),
+            instrument: self.instrument.clone(),
+            diagnostics: take(&mut self.diagnostics),
+            has_errors: take(&mut self.has_errors),
         }
     }
+    /// A parser for the parts of another instrument of the same score, which knows the directives
+    /// read so far.
+    pub(crate) fn for_instrument(&self, label: String) -> Self {
+        Self {
+            metadata: self.metadata.clone(),
+            declared_tuning: self.declared_tuning.clone(),
+            instrument: Some(label),
+            ..Self::with_settings(self.settings.clone())
+        }
+    }
+    pub(crate) fn instrument(&self) -> Option<&str> {
+        self.instrument.as_deref()
+    }
+    pub(crate) fn has_measures(&self) -> bool {
+        !self.measures.is_empty()
+    }
+    pub(crate) fn take_errors(&mut self) -> Vec<BackendError> {
+        std::mem::take(&mut self.errors)
+    }
     pub fn into_result(self) -> ParserResult {
-        let Parser { tick_stream, measures, base_notes, offsets } = self;
-        ParserResult { tick_stream, measures, base_notes, offsets }
+        let Parser {
+            tick_stream,
+            measures,
+            tuning,
+            offsets,
+            stray_lines,
+            annotations,
+            rhythm,
+            chords,
+            lyrics,
+            sections,
+            trailing,
+            spans,
+            string_count,
+            metadata,
+            instrument,
+            diagnostics,
+            has_errors,
+            ..
+        } = self;
+        ParserResult {
+            tick_stream,
+            measures,
+            tuning,
+            offsets,
+            stray_lines,
+            annotations,
+            rhythm,
+            chords,
+            lyrics,
+            sections,
+            trailing,
+            spans,
+            string_count,
+            metadata,
+            instrument,
+            diagnostics,
+            has_errors,
+        }
     }
     pub fn as_ref<'a>(&'a self) -> ParserRef<'a> {
-        let Parser { tick_stream, measures, base_notes, offsets } = self;
-        ParserRef { tick_stream, measures, base_notes, offsets }
+        let Parser {
+            tick_stream,
+            measures,
+            tuning,
+            offsets,
+            stray_lines,
+            annotations,
+            rhythm,
+            chords,
+            lyrics,
+            sections,
+            trailing,
+            spans,
+            string_count,
+            metadata,
+            ..
+        } = self;
+        let string_count = *string_count;
+        ParserRef {
+            tick_stream,
+            measures,
+            tuning,
+            offsets,
+            stray_lines,
+            annotations,
+            rhythm,
+            chords,
+            lyrics,
+            sections,
+            trailing,
+            spans,
+            string_count,
+            metadata,
+        }
     }
     #[inline(always)]
     fn parse_tab_element<'a>(
-        &self, part: &[&'a str; 6], s: usize, part_first_line: usize,
+        &self, part: &[&'a str], source: &[&str], s: usize, part_first_line: usize,
     ) -> Result<(&'a str, TabElement), BackendError> {
-        match tab_element3(part[s]) {
+        match tab_element3(part[s], &self.settings.dialect) {
             Ok(x) => Ok(x),
             Err((_, err)) => {
-                let (line, char) =
-                    source_location_while_parsing(self, part_first_line as u32, s as u32);
+                // the symbols of the dialect can have any width, so the column is only known from
+                // the line itself
+                let line = self.string_line(part_first_line, s) as u32;
+                let char = char_col_in(source[s], part[s]) as u32;
                 match err {
                     Some(TabElementError::FretTooLarge) => {
                         Err(BackendError::large_fret(line, char))
@@ -237,77 +1283,152 @@ impl Parser {
 
 impl ParserResult {
     pub fn into_parser(self) -> Parser {
-        let ParserResult { tick_stream, measures, base_notes, offsets } = self;
-        Parser { tick_stream, measures, base_notes, offsets }
+        let ParserResult {
+            tick_stream,
+            measures,
+            tuning,
+            offsets,
+            stray_lines,
+            annotations,
+            rhythm,
+            chords,
+            lyrics,
+            sections,
+            trailing,
+            spans,
+            string_count,
+            metadata,
+            instrument,
+            diagnostics,
+            has_errors,
+        } = self;
+        Parser {
+            tick_stream,
+            measures,
+            tuning,
+            offsets,
+            stray_lines,
+            annotations,
+            rhythm,
+            chords,
+            lyrics,
+            sections,
+            trailing,
+            spans,
+            string_count,
+            metadata,
+            instrument,
+            diagnostics,
+            has_errors,
+            ..Default::default()
+        }
     }
     pub fn as_ref<'a>(&'a self) -> ParserRef<'a> {
-        let ParserResult { tick_stream, measures, base_notes, offsets } = self;
-        ParserRef { tick_stream, measures, base_notes, offsets }
+        let ParserResult {
+            tick_stream,
+            measures,
+            tuning,
+            offsets,
+            stray_lines,
+            annotations,
+            rhythm,
+            chords,
+            lyrics,
+            sections,
+            trailing,
+            spans,
+            string_count,
+            metadata,
+            instrument: _,
+            diagnostics: _,
+            has_errors: _,
+        } = self;
+        let string_count = *string_count;
+        ParserRef {
+            tick_stream,
+            measures,
+            tuning,
+            offsets,
+            stray_lines,
+            annotations,
+            rhythm,
+            chords,
+            lyrics,
+            sections,
+            trailing,
+            spans,
+            string_count,
+            metadata,
+        }
+    }
+    /// The MIDI note number of an open string, taking the capo into account.
+    pub fn open_string_pitch(&self, string: usize) -> u8 {
+        self.tuning[string].saturating_add(self.metadata.capo.unwrap_or(0))
     }
 }
 pub fn dump_tracks(parser: &ParserRef) -> String {
+    let n = parser.string_count;
     let stream_len = parser.tick_stream.len();
-    let tick_cnt = stream_len / 6;
-    debug_assert_eq!(stream_len % 6, 0);
-    let mut bufs = vec![String::new(); 6];
+    let tick_cnt = stream_len.checked_div(n).unwrap_or(0);
+    debug_assert_eq!(stream_len.checked_rem(n).unwrap_or(0), 0);
+    let mut bufs = vec![String::new(); n];
     for tick in 0..tick_cnt {
         let max_width =
-            (0..6).map(|x| parser.tick_stream[tick * 6 + x].repr_len()).max().unwrap() as usize;
+            (0..n).map(|x| parser.tick_stream[tick * n + x].repr_len()).max().unwrap() as usize;
         for (s, buf) in bufs.iter_mut().enumerate() {
             use tab_element::TabElement::*;
             let to_padded = |c: char| format!("{1:<0$}", max_width, c);
-            match parser.tick_stream[tick * 6 + s] {
+            match parser.tick_stream[tick * n + s] {
                 Fret(x) => buf.push_str(&format!("{x:<0$}", max_width)),
+                NaturalHarmonic(x) => buf.push_str(&format!("{:<1$}", format!("<{x}>"), max_width)),
+                ArtificialHarmonic { fret, touch: None } => {
+                    buf.push_str(&format!("{:<1$}", format!("[{fret}]"), max_width))
+                }
+                ArtificialHarmonic { fret, touch: Some(touch) } => {
+                    buf.push_str(&format!("{:<1$}", format!("{fret}({touch})"), max_width))
+                }
                 Rest => buf.push_str(&to_padded('-')),
                 DeadNote => buf.push_str(&to_padded('x')),
-                Slide => buf.push_str(&to_padded('/')),
-                Bend => buf.push_str(&to_padded('b')),
+                Slide { down: false } => buf.push_str(&to_padded('/')),
+                Slide { down: true } => buf.push_str(&to_padded('\\')),
+                Bend { amount, pre } => {
+                    let amount = match amount {
+                        None => String::new(),
+                        Some(tab_element::BendAmount::Fret(x)) => x.to_string(),
+                        Some(tab_element::BendAmount::Enclosed(x)) => format!("({x})"),
+                        Some(tab_element::BendAmount::Half) => "½".into(),
+                    };
+                    let pre = if pre { "p" } else { "" };
+                    buf.push_str(&format!("{:<1$}", format!("{pre}b{amount}"), max_width))
+                }
                 HammerOn => buf.push_str(&to_padded('h')),
                 Pull => buf.push_str(&to_padded('p')),
-                Release => buf.push_str(&to_padded('r')),
+                Release { to: None } => buf.push_str(&to_padded('r')),
+                Release { to: Some(x) } => {
+                    buf.push_str(&format!("{:<1$}", format!("r{x}"), max_width))
+                }
                 Vibrato => buf.push_str(&to_padded('~')),
+                Tap => buf.push_str(&to_padded('t')),
+                Tremolo { tp: false } => buf.push_str(&to_padded('=')),
+                Trill => buf.push_str(&format!("{:<1$}", "tr", max_width)),
+                Tremolo { tp: true } => buf.push_str(&format!("{:<1$}", "TP", max_width)),
+                // written like in the classic dialect, as the standard one has no symbols for them
+                Accent => buf.push_str(&to_padded('*')),
+                Staccato => buf.push_str(&to_padded('.')),
+                GhostNote(x) => buf.push_str(&format!("{:<1$}", format!("({x})"), max_width)),
             }
         }
     }
     bufs.iter_mut().for_each(|x| x.push('\n'));
     bufs.concat()
 }
-/// A specialized, faster [source_location_from_stream]
-pub fn source_location_while_parsing(
-    r: &Parser, part_first_line: u32, line_in_part: u32,
-) -> (u32, u32) {
-    let actual_line = part_first_line + line_in_part;
-    trace!("expecting the error to be on line {actual_line}");
-    let error_tick = r.tick_stream.len() / 6;
-    // we aren't accounting for measures here, so sum of all the measure lines too
-    let part_start = r.offsets.last().map(|x| x.1).unwrap_or(0);
-    let mut measure_lines = 0;
-    for measure in r.measures.iter().rev() {
-        if measure.data_range.start() < &part_start {
-            break;
-        }
-        measure_lines += 1;
-    }
-    trace!("need to account for {measure_lines} measure lines");
-    let mut offset_on_line = 1 + measure_lines; // e|
-    let start = (part_start / 6) as usize;
-    for tick in start..error_tick {
-        // take the maximum extent of this tick. we cannot just add up the local tick lengths because multichars on *other strings* would throw off the parser
-        // -1-2-3-
-        // -11b12- <- this would think that if there is an error on the first string, the extents before are just rest-1-2-3-rest, and report an incorrect location
-        let remainder = &r.tick_stream[tick * 6..];
-        //trace!(?remainder);
-        let tick_width = remainder.iter().take(6).map(|x| x.repr_len()).max();
-        let tick_width = tick_width.unwrap_or(0);
-        trace!(offset = tick_width, tick, "adding offset for tick");
-        offset_on_line += tick_width;
-    }
-    offset_on_line += 1; // because the location refers to the offset of the tick that was not parsed
-    trace!("expecting the error to be at character idx {offset_on_line}");
-    (actual_line, offset_on_line)
-}
-
+/// Where the element at `tick_location` of the stream is in the source. Without
+/// [ParserSettings::spans], this is reconstructed from the widths the elements have in the standard
+/// dialect.
 pub fn source_location_from_stream(r: &ParserRef, tick_location: u32) -> (u32, u32) {
+    if let Some((line, span)) = r.source_span(tick_location) {
+        return (line, span.start as u32);
+    }
     let section = r
         .offsets
         .binary_search_by_key(&tick_location, |x| x.1)
@@ -316,17 +1437,19 @@ pub fn source_location_from_stream(r: &ParserRef, tick_location: u32) -> (u32, u
     let part_start = r.offsets[section].1;
     let idx_in_part = tick_location - part_start;
     trace!("this is the {idx_in_part}th element in the part");
-    let line_in_part = idx_in_part % 6;
-    let actual_line = r.offsets[section].0 + line_in_part;
+    let n = r.string_count as u32;
+    let line_in_part = idx_in_part % n;
+    let actual_line = string_line(r.stray_lines, r.offsets[section].0, line_in_part);
     trace!("expecting the error to be on line {actual_line}");
 
-    let error_tick = (tick_location / 6) as usize;
+    let error_tick = (tick_location / n) as usize;
     // we aren't accounting for measures here, so sum of all the measure lines to
     // search for all the measures in this part, and before the needle
+    // a tick right after the end of a measure starts the next one
     let last_measure = r
         .measures
         .binary_search_by_key(&tick_location, |x| x.data_range.end() + 1)
-        .unwrap_or_else(|x| x);
+        .map_or_else(|x| x, |x| x + 1);
     debug!("last measure we need to check: {last_measure} for needle {tick_location}");
     let mut measure_lines = 0;
     trace!("{:?}", r.measures);
@@ -341,14 +1464,14 @@ pub fn source_location_from_stream(r: &ParserRef, tick_location: u32) -> (u32, u
     measure_lines += 1; // for last measure; which we cannot index with 0..=last_measure if we have only 1.
     trace!("need to account for {measure_lines} measure lines");
     let mut offset_on_line = 1 + measure_lines; // e|
-    let start = (part_start / 6) as usize;
+    let start = (part_start / n) as usize;
     for tick in start..error_tick {
         // take the maximum extent of this tick. we cannot just add up the local tick lengths because multichars on *other strings* would throw off the parser
         // -1-2-3-
         // -11b12- <- this would think that if there is an error on the first string, the extents before are just rest-1-2-3-rest, and report an incorrect location
-        let remainder = &r.tick_stream[tick * 6..];
+        let remainder = &r.tick_stream[tick * n as usize..];
         //traceln!(depth = 1, "remainder: {remainder:?}");
-        let tick_width = remainder.iter().take(6).map(|x| x.repr_len()).max();
+        let tick_width = remainder.iter().take(n as usize).map(|x| x.repr_len()).max();
         let tick_width = tick_width.unwrap_or(0);
         trace!(offset = tick_width, tick, "adding offset for tick");
         offset_on_line += tick_width;
@@ -357,6 +1480,77 @@ pub fn source_location_from_stream(r: &ParserRef, tick_location: u32) -> (u32, u
     (actual_line, offset_on_line)
 }
 
+/// The lines directly above a part that describe it, like endings, rhythm and annotations.
+pub(crate) fn lines_above<L: ParseLines>(lines: &L, part_first_line: usize) -> Range<usize> {
+    let mut first = part_first_line;
+    while first > 0 && is_line_above(lines.get_line(first - 1)) {
+        first -= 1;
+    }
+    first..part_first_line
+}
+
+/// The line of string `string` of the part that starts at `part_line`. The strings under an empty
+/// line or a comment inside of the part are one line further down.
+pub fn string_line(stray_lines: &[u32], part_line: u32, string: u32) -> u32 {
+    let mut line = part_line + string;
+    let first = stray_lines.partition_point(|x| *x <= part_line);
+    for stray in &stray_lines[first..] {
+        if *stray > line {
+            break;
+        }
+        line += 1;
+    }
+    line
+}
+
+/// Whether this line describes the part under it
+pub(crate) fn is_line_above(line: &str) -> bool {
+    annotation_line(line).is_some()
+        || ending_line(line).is_some()
+        || rhythm_line(line).is_some()
+        || chord_line(line).is_some()
+}
+
+/// The index of the tick under column `col`, given the column where each tick starts
+fn tick_at_col(tick_cols: &[usize], col: usize) -> usize {
+    tick_cols.partition_point(|x| *x <= col).saturating_sub(1)
+}
+
+/// The number of characters that were read from `before` to leave `after`. Ticks are aligned by
+/// characters, as some elements like `b½` are wider in bytes.
+fn char_width(before: &str, after: &str) -> usize {
+    let read = &before[..before.len() - after.len()];
+    match read.is_ascii() {
+        true => read.len(),
+        false => read.chars().count(),
+    }
+}
+
+enum Side {
+    Start,
+    End,
+}
+
+/// If any string has a repeat sign (`:`) on the given side, removes that column from every string.
+/// The other strings may only have a rest there.
+fn strip_repeat_column(part: &mut [&str], side: Side) -> bool {
+    let char_at = |x: &str| match side {
+        Side::Start => x.bytes().next(),
+        Side::End => x.bytes().next_back(),
+    };
+    let has_sign = part.iter().any(|x| char_at(x) == Some(b':'));
+    if !has_sign || !part.iter().all(|x| matches!(char_at(x), Some(b':' | b'-'))) {
+        return false;
+    }
+    for string in part.iter_mut() {
+        *string = match side {
+            Side::Start => &string[1..],
+            Side::End => &string[..string.len() - 1],
+        };
+    }
+    true
+}
+
 pub fn dump_source(input: &[&str]) -> String {
     use itertools::Itertools;
     std::iter::once(&"").chain(input.iter()).join("\n")
@@ -1,5 +1,5 @@
 use crate::{
-    parser::{dump_tracks, Parser},
+    parser::{dump_tracks, settings::ParserSettings, tab_element::TabElement, Parser},
     BufLines,
 };
 
@@ -123,6 +123,65 @@ E|------12b-|
     insta::assert_snapshot!(dump_tracks(&parsed.as_ref()));
 }
 #[test]
+fn test_bend_targets() {
+    use crate::parser::tab_element::BendAmount::*;
+    let score = r#"
+e|7b9--|7b(9)|
+B|7b½--|7pb9-|
+G|7b1--|7b9r7|
+"#;
+    let parsed = Parser::parse(&BufLines::from_string(score.into())).unwrap();
+    insta::assert_snapshot!(dump_tracks(&parsed.as_ref()));
+    let bend = |amount| TabElement::Bend { amount: Some(amount), pre: false };
+    assert_eq!(parsed.tick_stream[3..6], [bend(Fret(9)), bend(Half), bend(Fret(1))]);
+    // frets above the bent one are targets, `b1` and `b½` count tones
+    assert_eq!(bend(Fret(9)).bend_target(7, 0), Some(2));
+    assert_eq!(bend(Enclosed(9)).bend_target(7, 0), Some(2));
+    assert_eq!(bend(Half).bend_target(7, 0), Some(1));
+    assert_eq!(bend(Fret(1)).bend_target(7, 0), Some(2));
+    assert_eq!(bend(Fret(1)).bend_target(7, 2), Some(4));
+    // other frets that aren't above it are not
+    assert_eq!(bend(Fret(12)).bend_target(12, 0), None);
+    assert_eq!(bend(Fret(2)).bend_target(3, 0), None);
+    assert_eq!(bend(Enclosed(1)).bend_target(7, 0), None);
+    assert_eq!(TabElement::Release { to: Some(7) }.bend_target(7, 2), Some(0));
+}
+#[test]
+fn test_dialects() {
+    use crate::parser::dialect::Dialect;
+    let score = r#"
+e|7^9-5s7|
+B|5*--X--|
+G|7v-----|
+"#;
+    let parse = |dialect| {
+        let settings = ParserSettings { dialect, ..Default::default() };
+        Parser::parse_with(&BufLines::from_string(score.into()), settings)
+    };
+    assert!(parse(Dialect::standard()).is_err());
+    insta::assert_snapshot!(dump_tracks(&parse(Dialect::classic()).unwrap().as_ref()));
+
+    // a symbol means the element it is mapped to, however long it is
+    let tremolo = |file: &str| {
+        let settings =
+            ParserSettings { dialect: Dialect::parse(file).unwrap(), ..Default::default() };
+        let lines = BufLines::from_string("e|5trem|\nB|-----|\nG|-----|\n".into());
+        Parser::parse_with(&lines, settings).unwrap().tick_stream[3].clone()
+    };
+    assert_eq!(tremolo("extends standard\ntrem tremolo"), TabElement::Tremolo { tp: false });
+    assert_eq!(tremolo("extends standard\ntrem tremolo-tp"), TabElement::Tremolo { tp: true });
+
+    // errors point at the character, whatever the width of the symbols before it
+    use crate::backend::errors::error_location::ErrorLocation;
+    let dialect = Dialect::parse("extends standard\n^^ bend").unwrap();
+    let lines = BufLines::from_string("e|7^^9-z-|\nB|-------|\nG|-------|\n".into());
+    for spans in [false, true] {
+        let settings = ParserSettings { dialect: dialect.clone(), spans, ..Default::default() };
+        let (err, _) = Parser::parse_with(&lines, settings).unwrap_err();
+        assert_eq!(err.main_location, ErrorLocation::LineAndChar(0, 7));
+    }
+}
+#[test]
 fn test_multichar_tracks() -> anyhow::Result<()> {
     let input = r#"
 e|----5--|
@@ -163,3 +222,971 @@ E|-----9-|-----9-|
     println!("Parser3 took: {:?}", time_parser3.elapsed());
     insta::assert_debug_snapshot!(parsed);
 }
+
+#[test]
+fn test_bass_part() {
+    let bass = r#"
+G|-------|-------|
+D|-----2-|-------|
+A|-0-3---|-----3-|
+E|-------|-0-3---|
+
+G|-------|
+D|-0-2-4-|
+A|-------|
+E|-------|
+"#;
+    let parsed = Parser::parse(&BufLines::from_string(bass.into())).unwrap();
+    assert_eq!(parsed.string_count, 4);
+    assert_eq!(parsed.offsets, [(1, 0), (6, 56)]);
+    insta::assert_snapshot!(dump_tracks(&parsed.as_ref()));
+}
+
+#[test]
+fn test_seven_string_part() {
+    let part = r#"
+e|-----|
+B|-----|
+G|-----|
+D|--12-|
+A|-----|
+E|-3---|
+B|0----|
+"#;
+    let parsed = Parser::parse(&BufLines::from_string(part.into())).unwrap();
+    assert_eq!(parsed.string_count, 7);
+    insta::assert_snapshot!(dump_tracks(&parsed.as_ref()));
+}
+
+#[test]
+fn test_string_count_mismatch() {
+    use crate::backend::errors::backend_error_kind::BackendErrorKind;
+    let score = r#"
+e|---|
+B|---|
+G|---|
+D|---|
+A|---|
+E|---|
+
+G|---|
+D|---|
+A|---|
+E|---|
+"#;
+    let (err, _) = Parser::parse(&BufLines::from_string(score.into())).unwrap_err();
+    assert!(matches!(err.kind, BackendErrorKind::StringCountMismatch { expected: 6, found: 4 }));
+    assert_eq!(err.relevant_lines, 8..=11);
+}
+
+#[test]
+fn test_stacked_parts() {
+    use crate::parser::stream::PartStream;
+    // the first parts of the file decide the string count, even without an empty line after them
+    let score = r#"e|0---|
+B|1---|
+G|0---|
+D|2---|
+A|3---|
+E|----|
+e|3---|
+B|0---|
+G|0---|
+D|0---|
+A|2---|
+E|3---|
+"#;
+    let parsed = Parser::parse(&BufLines::from_string(score.into())).unwrap();
+    assert_eq!(parsed.string_count, 6);
+    assert_eq!(parsed.offsets, [(0, 0), (6, 24)]);
+    assert_eq!(parsed.tuning, [64, 59, 55, 50, 45, 40]);
+    let streamed: Vec<_> = PartStream::new(score.as_bytes(), ParserSettings::default())
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(streamed.len(), 2);
+
+    // drop D names a note twice, which doesn't split the part
+    let drop_d = score.replace("E|", "D|");
+    let parsed = Parser::parse(&BufLines::from_string(drop_d)).unwrap();
+    assert_eq!(parsed.offsets, [(0, 0), (6, 24)]);
+    assert_eq!(parsed.tuning, [64, 59, 55, 50, 45, 38]);
+}
+
+#[test]
+fn test_tuning() {
+    let standard = r#"
+e|-0-|
+B|-0-|
+G|-0-|
+D|-0-|
+A|-0-|
+E|-0-|
+"#;
+    let parsed = Parser::parse(&BufLines::from_string(standard.into())).unwrap();
+    assert_eq!(parsed.tuning, [64, 59, 55, 50, 45, 40]);
+
+    let prefixed = r#"
+E4|-0-|
+B3|-0-|
+G3|-0-|
+D3|-0-|
+A2|-0-|
+D2|-0-|
+"#;
+    let parsed = Parser::parse(&BufLines::from_string(prefixed.into())).unwrap();
+    assert_eq!(parsed.tuning, [64, 59, 55, 50, 45, 38]);
+
+    let sharp = r#"
+c#|-0-|
+G#|-0-|
+E|-0-|
+B|-0-|
+F#|-0-|
+C#|-0-|
+"#;
+    let parsed = Parser::parse(&BufLines::from_string(sharp.into())).unwrap();
+    assert_eq!(parsed.tuning, [61, 56, 52, 47, 42, 37]);
+
+    let declared = r#"
+tuning: D2 A2 D3 G3 A3 D4
+
+e|-0-|
+B|-0-|
+G|-0-|
+D|-0-|
+A|-0-|
+E|-0-|
+"#;
+    let parsed = Parser::parse(&BufLines::from_string(declared.into())).unwrap();
+    assert_eq!(parsed.tuning, [62, 57, 55, 50, 45, 38]);
+}
+
+#[test]
+fn test_tuning_mismatch() {
+    use crate::backend::errors::backend_error_kind::BackendErrorKind;
+    let score = r#"
+tuning: E A D G
+e|-0-|
+B|-0-|
+G|-0-|
+D|-0-|
+A|-0-|
+E|-0-|
+"#;
+    let (err, _) = Parser::parse(&BufLines::from_string(score.into())).unwrap_err();
+    assert!(matches!(
+        err.kind,
+        BackendErrorKind::TuningMismatch { string_count: 6, tuning_len: 4 }
+    ));
+}
+
+#[test]
+fn test_metadata() {
+    use crate::{
+        backend::errors::backend_error_kind::BackendErrorKind, parser::metadata::ScoreMetadata,
+    };
+    let score = r#"
+Title: Romance
+Artist: Anonymous
+Tempo: 90 bpm
+Capo: 2nd fret
+Time: 3/4
+
+e|-0-|
+B|-0-|
+G|-0-|
+D|-0-|
+A|-0-|
+E|-0-|
+"#;
+    let parsed = Parser::parse(&BufLines::from_string(score.into())).unwrap();
+    assert_eq!(
+        parsed.metadata,
+        ScoreMetadata {
+            title: Some("Romance".into()),
+            artist: Some("Anonymous".into()),
+            tempo: Some(90),
+            capo: Some(2),
+            tuning: None,
+            time: Some((3, 4)),
+        }
+    );
+    assert_eq!(parsed.open_string_pitch(5), 42);
+
+    let (err, _) = Parser::parse(&BufLines::from_string("time: 3/5".into())).unwrap_err();
+    assert_eq!(err.kind.desc().0, "Invalid time");
+    // numbers that don't fit in a byte are errors, not wrapped around
+    let (err, _) = Parser::parse(&BufLines::from_string("capo: 259".into())).unwrap_err();
+    assert_eq!(err.kind.desc().0, "Invalid capo");
+    let (err, _) =
+        Parser::parse(&BufLines::from_string("e|256|\nB|---|\nG|---|".into())).unwrap_err();
+    assert!(matches!(err.kind, BackendErrorKind::FretTooLarge));
+    // `:` comes right after the digits, but isn't one
+    let (err, _) =
+        Parser::parse(&BufLines::from_string("e|0:0|\nB|---|\nG|---|".into())).unwrap_err();
+    assert!(matches!(err.kind, BackendErrorKind::Parse3InvalidCharacter(Some(':'))));
+}
+
+#[test]
+fn test_repeats() {
+    use crate::parser::playback_order;
+    let score = r#"
+e|:-0-|-1-:|-2-|
+B|:---|---:|---|
+G|:---|---:|---|
+D|:---|---:|---|
+A|:---|---:|---|
+E|:---|---:|---|
+
+e|-3-|-4-|  x3
+B|---|---|
+G|---|---|
+D|---|---|
+A|---|---|
+E|---|---|
+"#;
+    let parsed = Parser::parse(&BufLines::from_string(score.into())).unwrap();
+    insta::assert_snapshot!(dump_tracks(&parsed.as_ref()));
+    let flags: Vec<_> =
+        parsed.measures.iter().map(|m| (m.repeat_start, m.repeat_end, m.ending)).collect();
+    assert_eq!(
+        flags,
+        [
+            (true, None, None),
+            (false, Some(2), None),
+            (false, None, None),
+            (true, None, None),
+            (false, Some(3), None)
+        ]
+    );
+    assert_eq!(playback_order(&parsed.measures), [0, 1, 0, 1, 2, 3, 4, 3, 4, 3, 4]);
+}
+
+#[test]
+fn test_endings() {
+    use crate::parser::playback_order;
+    let score = r#"
+           1.    2.
+e|:-0-|-1-|-2-:|-3-|
+B|----|---|----|---|
+G|----|---|----|---|
+D|----|---|----|---|
+A|----|---|----|---|
+E|----|---|----|---|
+"#;
+    let parsed = Parser::parse(&BufLines::from_string(score.into())).unwrap();
+    let endings: Vec<_> = parsed.measures.iter().map(|m| m.ending).collect();
+    assert_eq!(endings, [None, None, Some(1), Some(2)]);
+    assert_eq!(playback_order(&parsed.measures), [0, 1, 2, 0, 1, 3]);
+}
+#[test]
+fn test_harmonics() {
+    use crate::parser::tab_element::TabElement::*;
+    let score = r#"
+e|<12>---|-[7]-|
+B|-------|-----|
+G|----<7>|-----|
+D|-------|7(19)|
+A|-------|-----|
+E|-------|-----|
+"#;
+    let parsed = Parser::parse(&BufLines::from_string(score.into())).unwrap();
+    let notes: Vec<_> = parsed.tick_stream.iter().filter(|x| !matches!(x, Rest)).cloned().collect();
+    assert_eq!(
+        notes,
+        [
+            NaturalHarmonic(12),
+            NaturalHarmonic(7),
+            ArtificialHarmonic { fret: 7, touch: None },
+            ArtificialHarmonic { fret: 7, touch: Some(19) }
+        ]
+    );
+    // <12> sounds an octave above the open string and <7> an octave and a fifth. [7] and 7(19)
+    // both sound an octave above the fretted note
+    let sounding: Vec<_> = notes.iter().map(|x| x.sounding_offset().unwrap()).collect();
+    assert_eq!(sounding, [12, 19, 19, 19]);
+    // there is no node at the 15th fret, so it is played like an artificial harmonic
+    assert_eq!(NaturalHarmonic(15).sounding_offset(), Some(27));
+    assert_eq!(NaturalHarmonic(16).sounding_offset(), Some(28));
+    insta::assert_snapshot!(dump_tracks(&parsed.as_ref()));
+}
+#[test]
+fn test_annotations() {
+    use crate::parser::annotation::{Annotation, AnnotationKind::*};
+    let score = r#"
+  PM------|let ring
+e|0-0-0-0-|0-----|
+B|--------|------|
+G|--------|------|
+D|--------|------|
+A|--------|------|
+E|--------|------|
+  P.M. - - |
+e|0-0-0-0-|
+B|--------|
+G|--------|
+D|--------|
+A|--------|
+E|--------|
+"#;
+    let parsed = Parser::parse(&BufLines::from_string(score.into())).unwrap();
+    assert_eq!(
+        parsed.annotations,
+        [
+            Annotation { kind: PalmMute, ticks: 0..=7 },
+            Annotation { kind: LetRing, ticks: 8..=13 },
+            Annotation { kind: PalmMute, ticks: 14..=21 },
+        ]
+    );
+}
+#[test]
+fn test_techniques() {
+    let score = r#"
+e|5h7t12p5|-------|
+B|--------|(5)----|
+G|--------|---7tr-|
+D|--------|-----9=|
+A|--------|---3TP-|
+E|--------|-------|
+"#;
+    let parsed = Parser::parse(&BufLines::from_string(score.into())).unwrap();
+    insta::assert_snapshot!(dump_tracks(&parsed.as_ref()));
+}
+#[test]
+fn test_rhythm() {
+    use crate::parser::rhythm::{NoteValue::*, Rhythm};
+    let score = r#"
+  Q   E.S E3E3E3|H
+e|0---2-3-5-5-5-|7---|
+B|--------------|----|
+G|--------------|----|
+D|--------------|----|
+A|--------------|----|
+E|--------------|----|
+"#;
+    let parsed = Parser::parse(&BufLines::from_string(score.into())).unwrap();
+    let triplet = Some(Rhythm { value: Eighth, dots: 0, triplet: true });
+    #[rustfmt::skip]
+    assert_eq!(
+        parsed.rhythm,
+        [
+            Some(Rhythm::new(Quarter)), None, None, None,
+            Some(Rhythm { value: Eighth, dots: 1, triplet: false }), None,
+            Some(Rhythm::new(Sixteenth)), None,
+            triplet, None, triplet, None, triplet, None,
+            Some(Rhythm::new(Half)), None, None, None,
+        ]
+    );
+    assert!(parsed.measures.iter().all(|x| x.has_rhythm));
+    // a part without a rhythm line is all eighths
+    let parsed =
+        Parser::parse(&BufLines::from_string(score.lines().skip(2).collect::<Vec<_>>().join("\n")))
+            .unwrap();
+    assert!(parsed.rhythm.iter().all(|x| *x == Some(Rhythm::EIGHTH)));
+}
+#[test]
+fn test_chords() {
+    let score = r#"
+  Am       G/B
+e|0---0---|3---|
+B|1---1---|0---|
+G|2---2---|0---|
+D|2---2---|0---|
+A|0---0---|2---|
+E|--------|----|
+"#;
+    let parsed = Parser::parse(&BufLines::from_string(score.into())).unwrap();
+    let chords: Vec<_> = parsed.chords.iter().map(|(tick, x)| (*tick, x.to_string())).collect();
+    assert_eq!(chords, [(0, "Am".to_string()), (8, "G/B".to_string())]);
+}
+
+#[test]
+fn test_lyrics() {
+    use crate::parser::lyrics::Syllabic;
+    let score = r#"
+e|0---0---|3---|
+B|1---1---|0---|
+G|2---2---|0---|
+D|2---2---|0---|
+A|0---0---|2---|
+E|--------|----|
+  Hel-lo   world
+"#;
+    let parsed = Parser::parse(&BufLines::from_string(score.into())).unwrap();
+    let lyrics: Vec<_> =
+        parsed.lyrics.iter().map(|(tick, x)| (*tick, x.text.as_str(), x.syllabic)).collect();
+    assert_eq!(
+        lyrics,
+        [(0, "Hel", Syllabic::Begin), (4, "lo", Syllabic::End), (8, "world", Syllabic::Single)]
+    );
+
+    // columns count characters, so accented letters take one column like the dashes above them
+    let accented = score.replace("  Hel-lo   world", "  Dé- jà   vu");
+    let parsed = Parser::parse(&BufLines::from_string(accented)).unwrap();
+    let lyrics: Vec<_> =
+        parsed.lyrics.iter().map(|(tick, x)| (*tick, x.text.as_str(), x.syllabic)).collect();
+    assert_eq!(
+        lyrics,
+        [(0, "Dé", Syllabic::Begin), (4, "jà", Syllabic::End), (8, "vu", Syllabic::Single)]
+    );
+}
+
+#[test]
+fn test_sections() {
+    use crate::parser::stream::PartStream;
+    let score = r#"[Intro]
+e|0---|3---|
+B|1---|0---|
+G|2---|0---|
+D|2---|0---|
+A|0---|2---|
+E|----|----|
+
+Verse 1:
+  Am
+e|0---|
+B|1---|
+G|2---|
+D|2---|
+A|0---|
+E|----|
+"#;
+    let parsed = Parser::parse(&BufLines::from_string(score.into())).unwrap();
+    assert_eq!(parsed.sections, [(0, "Intro".to_string()), (2, "Verse 1".to_string())]);
+    // the heading isn't a chord or a lyric
+    assert_eq!(parsed.chords.len(), 1);
+    assert!(parsed.lyrics.is_empty());
+
+    let parts: Vec<_> = PartStream::new(score.as_bytes(), ParserSettings::default())
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let sections: Vec<_> = parts.iter().map(|x| x.sections.clone()).collect();
+    assert_eq!(sections, [vec![(0, "Intro".to_string())], vec![(0, "Verse 1".to_string())]]);
+}
+
+#[test]
+fn test_stray_lines() {
+    use crate::backend::errors::{diagnostic_kind::DiagnosticKind, error_location::ErrorLocation};
+    use crate::parser::stream::PartStream;
+    let score = r#"
+e|0---|3---|
+B|1---|0---|
+G|2---|0---|
+D|2---|0---|
+A|0---|2---|
+E|----|----|
+
+e|0---|
+// a comment
+B|1---|
+G|2---|
+D|2---|
+A|0---|
+E|----|
+  la
+"#;
+    let lines = BufLines::from_string(score.into());
+    let settings = ParserSettings { spans: true, ..Default::default() };
+    let parsed = Parser::parse_with(&lines, settings.clone()).unwrap();
+    assert_eq!(parsed.offsets, [(1, 0), (8, 48)]);
+    assert_eq!(parsed.stray_lines, [9]);
+    assert_eq!(parsed.measures.len(), 3);
+    assert_eq!(parsed.tick_stream[48..54], parsed.tick_stream[0..6]);
+    assert_eq!(parsed.diagnostics.len(), 1);
+    assert!(matches!(parsed.diagnostics[0].kind, DiagnosticKind::CommentInPart));
+    assert_eq!(parsed.diagnostics[0].location, ErrorLocation::LineOnly(9));
+    // the strings under the comment are one line further down
+    let parsed = parsed.as_ref();
+    assert_eq!(parsed.source_span(49), Some((10, 2..3)));
+    assert_eq!(parsed.element_at(10, 2), Some(49));
+    assert_eq!(parsed.element_at(9, 2), None);
+    assert_eq!(parsed.lyrics.len(), 1);
+
+    let score = score.replace("// a comment", "");
+    let lines = BufLines::from_string(score.clone());
+    let parsed = Parser::parse(&lines).unwrap();
+    assert_eq!(parsed.diagnostics.len(), 1);
+    assert!(matches!(parsed.diagnostics[0].kind, DiagnosticKind::EmptyLineInPart));
+    // errors under the empty line point at their string
+    let lines = BufLines::from_string(score.replace("A|0---|\nE", "A|0-z-|\nE"));
+    let (err, _) = Parser::parse_with(&lines, settings).unwrap_err();
+    assert_eq!(err.main_location, ErrorLocation::LineAndChar(13, 4));
+
+    let parts: Vec<_> = PartStream::new(score.as_bytes(), ParserSettings::default())
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(parts[1].stray_lines, [9]);
+    assert_eq!(parts[1].tick_stream[..6], parts[0].tick_stream[..6]);
+}
+
+#[test]
+fn test_misaligned_barlines() {
+    use crate::backend::errors::{
+        backend_error_kind::BackendErrorKind, error_location::ErrorLocation,
+    };
+    let score = r#"
+e|0---|3---|5---|
+B|1---|0---|5---|
+G|2---|0---|5---|
+D|2---|0---|5---|
+A|0---|2---|5---|
+E|----|----|5---|
+"#;
+    assert!(Parser::parse(&BufLines::from_string(score.into())).is_ok());
+    let lines = BufLines::from_string(score.replace("G|2---|0---|", "G|2---|0--|-"));
+    let (err, _) = Parser::parse(&lines).unwrap_err();
+    assert_eq!(err.main_location, ErrorLocation::LineAndMeasure(3, 1));
+    assert!(matches!(err.kind, BackendErrorKind::MisalignedBarline { string: 2 }));
+
+    // a string that goes on after the others end
+    let lines = BufLines::from_string(score.replace("E|----|----|5---|", "E|----|----|5----|"));
+    let (err, _) = Parser::parse(&lines).unwrap_err();
+    assert_eq!(err.main_location, ErrorLocation::LineAndMeasure(6, 2));
+
+    // when the first string is the odd one out, it is the one reported
+    let lines = BufLines::from_string(score.replace("e|0---|", "e|0--|"));
+    let (err, _) = Parser::parse(&lines).unwrap_err();
+    assert_eq!(err.main_location, ErrorLocation::LineAndMeasure(1, 0));
+
+    // only the misaligned measure is dropped
+    let settings = ParserSettings { recover: true, ..Default::default() };
+    let (parsed, errors) = Parser::parse_all(&lines, settings);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(parsed.measures.len(), 2);
+}
+
+#[test]
+fn test_trailing() {
+    use crate::parser::{
+        incremental::TextEdit,
+        trailing::{Trailing, TrailingAnnotation},
+    };
+    let score = r#"
+e|0---|3---|  (x2)
+B|1---|0---|
+G|2---|0---|
+D|2---|0---|
+A|0---|2---|
+E|----|3---|  <- let ring
+
+e|0---|
+B|1---|
+G|2---|
+D|2---|
+A|0---|
+E|----|
+"#;
+    let parsed = Parser::parse(&BufLines::from_string(score.into())).unwrap();
+    let let_ring = Trailing::Text("let ring".into());
+    assert_eq!(
+        parsed.trailing,
+        [
+            TrailingAnnotation { line: 1, measure: 1, trailing: Trailing::RepeatCount(2) },
+            TrailingAnnotation { line: 6, measure: 1, trailing: let_ring.clone() }
+        ]
+    );
+    assert_eq!(parsed.measures[1].repeat_end, Some(2));
+    assert_eq!(parsed.measures[2].repeat_end, None);
+
+    // reusing the part after an edit below it keeps its text
+    let edit = TextEdit { lines: 12..13, text: "E|--3-|".into() };
+    let lines = BufLines::from_string(score.replace("E|----|\n", "E|--3-|\n"));
+    let reparsed = Parser::reparse(parsed, &lines, &edit, ParserSettings::default()).unwrap();
+    assert_eq!(reparsed.trailing[1].trailing, let_ring);
+}
+
+#[test]
+fn test_recovery() {
+    let score = r#"
+e|0---|0-y-|3---|
+B|1---|1---|0---|
+G|2---|2---|0---|
+D|2---|2---|0---|
+A|0---|0---|2---|
+E|----|----|----|
+
+e|0---|3---
+B|1---|0---|
+G|2---|0---|
+D|2---|0---|
+A|0---|2---|
+E|----|----|
+
+e|5---|
+B|----|
+G|----|
+D|----|
+A|----|
+E|----|
+"#;
+    let lines = BufLines::from_string(score.into());
+    let (parsed, errors) = Parser::parse_all(&lines, ParserSettings::default());
+    assert_eq!(errors.len(), 1);
+    assert_eq!(parsed.measures.len(), 1);
+
+    let settings = ParserSettings { recover: true, ..Default::default() };
+    let (parsed, errors) = Parser::parse_all(&lines, settings);
+    let kinds: Vec<_> = errors.iter().map(|x| x.kind.desc().0).collect();
+    assert_eq!(kinds.len(), 2, "{kinds:?}");
+    assert_eq!(errors[0].main_location.get_line_idx(), Some(1));
+    assert_eq!(errors[1].main_location.get_line_idx(), Some(9));
+    // the measure and the part with an error are skipped
+    assert_eq!(parsed.measures.len(), 3);
+    let firsts: Vec<_> = parsed
+        .measures
+        .iter()
+        .map(|x| parsed.tick_stream[*x.data_range.start() as usize].clone())
+        .collect();
+    assert_eq!(firsts, [TabElement::Fret(0), TabElement::Fret(3), TabElement::Fret(5)]);
+    assert_eq!(parsed.rhythm.len(), parsed.tick_stream.len() / 6);
+}
+
+#[test]
+fn test_stream() {
+    use crate::parser::stream::PartStream;
+    let score = r#"tempo: 100
+  Am
+e|0---0---|3---|
+B|1---1---|0---|
+G|2---2---|0---|
+D|2---2---|0---|
+A|0---0---|2---|
+E|--------|----|
+  PM-------
+e|5---|
+B|5---|
+G|6---|
+D|7---|
+A|7---|
+E|5---|
+e|3---|
+B|3---|
+G|4---|
+D|5---|
+A|5---|
+E|3---|
+  la
+"#;
+    let whole = Parser::parse(&BufLines::from_string(score.into())).unwrap();
+    let parts: Vec<_> = PartStream::new(score.as_bytes(), ParserSettings::default())
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(parts.len(), 3);
+    let ticks: Vec<_> = parts.iter().flat_map(|x| x.tick_stream.clone()).collect();
+    assert_eq!(ticks, whole.tick_stream);
+    let measures: usize = parts.iter().map(|x| x.measures.len()).sum();
+    assert_eq!(measures, whole.measures.len());
+    assert_eq!(parts[0].offsets, [(2, 0)]);
+    assert_eq!(parts[2].offsets, [(15, 0)]);
+    assert_eq!(parts[0].chords.len(), 1);
+    assert_eq!(parts[1].annotations.len(), 1);
+    assert_eq!(parts[2].lyrics.len(), 1);
+    assert!(parts.iter().all(|x| x.tuning == whole.tuning && x.metadata.tempo == Some(100)));
+
+    let invalid = score.replace("G|6---|", "G|6-y-|");
+    let mut stream = PartStream::new(invalid.as_bytes(), ParserSettings::default());
+    assert!(stream.next().unwrap().is_ok());
+    assert!(stream.next().unwrap().is_err());
+    assert!(stream.next().is_none());
+}
+
+#[test]
+fn test_instruments() {
+    use crate::{
+        backend::errors::backend_error_kind::BackendErrorKind, parser::stream::PartStream,
+    };
+    let score = r#"tempo: 100
+Gtr I
+e|0---|3---|
+B|1---|0---|
+G|0---|0---|
+D|2---|0---|
+A|3---|2---|
+E|----|3---|
+  la  la
+Bass:
+G|----|----|
+D|----|----|
+A|3---|----|
+E|----|3---|
+
+Gtr I
+e|0---|
+B|1---|
+G|0---|
+D|2---|
+A|3---|
+E|----|
+"#;
+    let lines = BufLines::from_string(score.into());
+    let (parsed, errors) = Parser::parse_instruments(&lines, ParserSettings::default());
+    assert!(errors.is_empty());
+    let names: Vec<_> = parsed.iter().map(|x| x.instrument.as_deref()).collect();
+    assert_eq!(names, [Some("Gtr I"), Some("Bass")]);
+    assert_eq!(parsed[0].string_count, 6);
+    assert_eq!(parsed[0].offsets, [(2, 0), (16, 48)]);
+    assert_eq!(parsed[0].lyrics.len(), 2);
+    assert_eq!(parsed[1].string_count, 4);
+    assert_eq!(parsed[1].offsets, [(10, 0)]);
+    assert!(parsed.iter().all(|x| x.metadata.tempo == Some(100)));
+
+    let parts: Vec<_> = PartStream::new(score.as_bytes(), ParserSettings::default())
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let names: Vec<_> = parts.iter().map(|x| x.instrument.as_deref()).collect();
+    assert_eq!(names, [Some("Gtr I"), Some("Bass"), Some("Gtr I")]);
+    assert!(parts.iter().all(|x| x.metadata.tempo == Some(100)));
+
+    // without labels, there is a single instrument, and mixing string counts in it is an error
+    let lines = BufLines::from_string(score.replace("Gtr I\n", "").replace("Bass:\n", "\n"));
+    let (parsed, errors) = Parser::parse_instruments(&lines, ParserSettings::default());
+    assert_eq!((parsed.len(), errors.len()), (1, 1));
+    assert!(matches!(
+        errors[0].kind,
+        BackendErrorKind::StringCountMismatch { expected: 6, found: 4 }
+    ));
+    assert_eq!(parsed[0].instrument, None);
+}
+
+#[test]
+fn test_reparse() {
+    use crate::parser::incremental::TextEdit;
+    let score = r#"title: Reparse
+  Am
+e|0---0---|3---|
+B|1---1---|0---|
+G|2---2---|0---|
+D|2---2---|0---|
+A|0---0---|2---|
+E|--------|----|
+
+  PM-------
+e|5---|7-7-|
+B|5---|----|
+G|6---|----|
+D|7---|----|
+A|7---|----|
+E|5---|----|
+  la  la
+
+e|3---|
+B|3---|
+G|4---|
+D|5---|
+A|5---|
+E|3---|"#;
+    let edits = [
+        (10..11, "e|5---|7-9-|"),
+        (9..10, ""),
+        (8..8, "  let ring --\n"),
+        (16..17, "  la-la\n  more text"),
+        (0..1, "tempo: 90\n"),
+        (2..3, "e|0---0---|5---|\n"),
+        (17..19, "e|3-|\nB|3-|\n"),
+        (19..20, "B|3---|\nB|3---|\n"),
+        (3..8, ""),
+    ];
+    for (lines, text) in edits {
+        let before: Vec<String> = score.lines().map(String::from).collect();
+        let mut after = before.clone();
+        after.splice(lines.clone(), text.lines().map(String::from));
+        let edit = TextEdit { lines, text: text.into() };
+        let previous = Parser::parse(&before).unwrap();
+        let reparsed = Parser::reparse(previous, &after, &edit, ParserSettings::default());
+        let expected = Parser::parse(&after);
+        assert_eq!(format!("{reparsed:?}"), format!("{expected:?}"), "after {edit:?}");
+    }
+
+    // the previous parse stopped at an error, as it does while a part is being typed
+    let before: Vec<String> =
+        score.replace("e|5---|7-7-|", "e|0-y-|").lines().map(String::from).collect();
+    let (_, previous) = Parser::parse(&before).unwrap_err();
+    let mut after = before.clone();
+    after[0] = "title: Edited".into();
+    let edit = TextEdit { lines: 0..1, text: "title: Edited".into() };
+    let reparsed = Parser::reparse(*previous, &after, &edit, ParserSettings::default());
+    let expected = Parser::parse(&after);
+    assert_eq!(format!("{reparsed:?}"), format!("{expected:?}"));
+}
+
+#[test]
+fn test_lenient() {
+    let strict = r#"
+  PM---
+e|--3-|0-|
+B|----|--|
+G|-0--|--|
+D|----|--|
+A|----|--|
+E|----|--|
+"#;
+    let lenient = r#"
+  PM---
+|--3-|0-|
+|----|--|
+|-0--|--|
+|----|--|
+|----|--|
+|----|--|
+
+> e|1-|
+> B|--|
+> G|--|
+> D|--|
+> A|--|
+> E|--|
+
+e-|2-|
+B:--|
+G ||--|
+D|--|
+A|--|
+E|--|
+"#;
+    let lines = BufLines::from_string(lenient.into());
+    // only the last three strings look like strings without the lenient detector
+    assert_eq!(Parser::parse(&lines).unwrap().string_count, 3);
+
+    let settings = ParserSettings { lenient: true, ..Default::default() };
+    let parsed = Parser::parse_with(&lines, settings).unwrap();
+    let expected = Parser::parse(&BufLines::from_string(strict.into())).unwrap();
+    assert_eq!(parsed.tuning, expected.tuning);
+    assert_eq!(parsed.tick_stream[..expected.tick_stream.len()], expected.tick_stream);
+    assert_eq!(parsed.annotations.len(), 1);
+    assert_eq!(parsed.measures.len(), 4);
+    // one for each string line that isn't `e|`, and one for the tuning
+    use crate::backend::errors::diagnostic_kind::DiagnosticKind;
+    let assumed_tuning = parsed
+        .diagnostics
+        .iter()
+        .filter(|x| matches!(x.kind, DiagnosticKind::AssumedStandardTuning { string_count: 6 }));
+    assert_eq!(assumed_tuning.count(), 1);
+    assert_eq!(parsed.diagnostics.len(), 6 + 6 + 3 + 1);
+}
+
+#[test]
+fn test_normalized_input() {
+    use crate::{
+        backend::errors::error_location::ErrorLocation, parser::normalize::SourceMap, ParseLines,
+    };
+    let clean = "e|-0--|\nB|----|\nG|----|\nD|----|\nA|----|\nE|----|\n";
+    let pasted = "e|–0——|\r\nB│----│\r\nG|----|\r\nD|----|\r\nA|----|\r\nE|----|\r\n";
+    let (lines, _) = SourceMap::normalize(pasted.as_bytes());
+    let parsed = Parser::parse(&lines).unwrap();
+    let expected = Parser::parse(&BufLines::from_string(clean.into())).unwrap();
+    assert_eq!(parsed.tick_stream, expected.tick_stream);
+
+    // columns count characters of the input, where the dash before the error takes three bytes
+    let (lines, map) = SourceMap::normalize("e|–z|\nB|--|\nG|--|".as_bytes());
+    let (err, _) = Parser::parse(&lines).unwrap_err();
+    assert_eq!(err.main_location, ErrorLocation::LineAndChar(0, 3));
+    assert_eq!(map.original_location(&err.main_location), ErrorLocation::LineAndChar(0, 3));
+    assert_eq!(map.original_lines(&lines).get_line(0), "e|–z|");
+}
+
+#[test]
+fn test_spans() {
+    let score = r#"
+  e|-0-|12-|
+  B|---|-3-|
+  G|-2-|---|
+"#;
+    let lines = BufLines::from_string(score.into());
+    let settings = ParserSettings { spans: true, ..Default::default() };
+    let parsed = Parser::parse_with(&lines, settings).unwrap();
+    let parsed = parsed.as_ref();
+    assert_eq!(parsed.spans.len(), parsed.tick_stream.len());
+    // the `0` on the first string
+    assert_eq!(parsed.source_span(3), Some((1, 5..6)));
+    // the `12` takes the whole tick, and the `3` fills it along with a rest
+    let twelve = parsed.element_at(1, 9).unwrap();
+    assert_eq!(parsed.tick_stream[twelve as usize], TabElement::Fret(12));
+    assert_eq!(parsed.source_span(twelve), Some((1, 8..10)));
+    assert_eq!(parsed.source_span(twelve + 1), Some((2, 8..10)));
+    assert_eq!(parsed.element_at(2, 9), Some(twelve + 1));
+    // barlines and the string names are not elements
+    assert_eq!(parsed.element_at(1, 7), None);
+    assert_eq!(parsed.element_at(1, 2), None);
+
+    // errors point at the character, even behind the indentation
+    let lines = BufLines::from_string(score.replace("-2-|", "-z-|"));
+    let settings = ParserSettings { spans: true, ..Default::default() };
+    let (err, _) = Parser::parse_with(&lines, settings).unwrap_err();
+    use crate::backend::errors::error_location::ErrorLocation;
+    assert_eq!(err.main_location, ErrorLocation::LineAndChar(3, 5));
+}
+
+#[test]
+fn test_spans_in_normalized_input() {
+    use crate::{backend::errors::error_location::ErrorLocation, parser::normalize::SourceMap};
+    // indented with a tab, with dashes that are several bytes long and a bend to `½`
+    let score = "\te|–0——|12–|\n\tB|————|–3–|\n\tG|–2b½|———|\n";
+    let original = |line: usize, span: std::ops::Range<usize>| -> String {
+        let line = score.split('\n').nth(line).unwrap();
+        line.chars().skip(span.start).take(span.len()).collect()
+    };
+    let (lines, map) = SourceMap::normalize(score.as_bytes());
+    let settings = ParserSettings { spans: true, ..Default::default() };
+    let parsed = Parser::parse_with(&lines, settings.clone()).unwrap();
+    let parsed = parsed.as_ref();
+    // spans count the characters of the normalised line, where the tab is eight spaces
+    let twelve = parsed.element_at(0, 16).unwrap();
+    assert_eq!(parsed.tick_stream[twelve as usize], TabElement::Fret(12));
+    let (line, span) = parsed.source_span(twelve).unwrap();
+    assert_eq!((line, span.clone()), (0, 15..17));
+    assert_eq!(map.original_span(line, span.clone()), 8..10);
+    assert_eq!(original(0, map.original_span(line, span)), "12");
+    let bend = parsed.element_at(2, 13).unwrap();
+    let (line, span) = parsed.source_span(bend).unwrap();
+    assert_eq!((line, span.clone()), (2, 12..14));
+    assert_eq!(original(2, map.original_span(line, span)), "b½");
+    // the rest under the `12` takes its width
+    let (line, span) = parsed.source_span(twelve + 2).unwrap();
+    assert_eq!(original(line as usize, map.original_span(line, span)), "——");
+
+    // errors count characters too, even after the `½`
+    let (lines, map) = SourceMap::normalize(score.replace("|———|\n", "|—z—|\n").as_bytes());
+    let (err, _) = Parser::parse_with(&lines, settings).unwrap_err();
+    assert_eq!(err.main_location, ErrorLocation::LineAndChar(2, 16));
+    assert_eq!(map.original_location(&err.main_location), ErrorLocation::LineAndChar(2, 9));
+}
+
+#[test]
+fn test_multichar_alignment() {
+    let parse = |score: &str| {
+        let parsed = Parser::parse(&BufLines::from_string(score.into())).unwrap();
+        dump_tracks(&parsed.as_ref())
+    };
+    // technique chains of different widths, aligned at both ends
+    insta::assert_snapshot!(parse(
+        r#"
+e|-12h14-|
+B|--5h7--|
+G|-------|
+"#
+    ));
+    // a string with two notes under a wider element gets a tick for each of them
+    insta::assert_snapshot!(parse(
+        r#"
+e|12h14|
+B|5h7--|
+G|-----|
+"#
+    ));
+    // a three-wide bend against single notes
+    insta::assert_snapshot!(parse(
+        r#"
+e|10b12-|
+B|-5----|
+G|---7--|
+"#
+    ));
+    // elements that overlap without starting together make one wide tick
+    insta::assert_snapshot!(parse(
+        r#"
+e|12---|
+B|-10--|
+G|--<7>|
+"#
+    ));
+}
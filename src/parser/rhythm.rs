@@ -0,0 +1,126 @@
+use super::char_col_in;
+
+/// Durations are measured in this fraction of a quarter note. This is fine enough for double
+/// dotted thirty-second notes and triplets of them.
+pub const PER_QUARTER: u32 = 96;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NoteValue {
+    Whole,
+    Half,
+    Quarter,
+    Eighth,
+    Sixteenth,
+    ThirtySecond,
+}
+impl NoteValue {
+    fn from_letter(c: char) -> Option<NoteValue> {
+        use NoteValue::*;
+        Some(match c.to_ascii_uppercase() {
+            'W' => Whole,
+            'H' => Half,
+            'Q' => Quarter,
+            'E' => Eighth,
+            'S' => Sixteenth,
+            'T' => ThirtySecond,
+            _ => return None,
+        })
+    }
+    /// The name of the note value in MusicXML's `<type>`
+    pub fn name(&self) -> &'static str {
+        use NoteValue::*;
+        match self {
+            Whole => "whole",
+            Half => "half",
+            Quarter => "quarter",
+            Eighth => "eighth",
+            Sixteenth => "16th",
+            ThirtySecond => "32nd",
+        }
+    }
+    pub fn duration(&self) -> u32 {
+        use NoteValue::*;
+        match self {
+            Whole => PER_QUARTER * 4,
+            Half => PER_QUARTER * 2,
+            Quarter => PER_QUARTER,
+            Eighth => PER_QUARTER / 2,
+            Sixteenth => PER_QUARTER / 4,
+            ThirtySecond => PER_QUARTER / 8,
+        }
+    }
+}
+
+/// The written length of a tick, like a dotted quarter or a triplet eighth.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rhythm {
+    pub value: NoteValue,
+    pub dots: u8,
+    pub triplet: bool,
+}
+impl Rhythm {
+    /// Ticks are eighths unless a rhythm line says otherwise
+    pub const EIGHTH: Rhythm = Rhythm::new(NoteValue::Eighth);
+    pub const fn new(value: NoteValue) -> Self {
+        Self { value, dots: 0, triplet: false }
+    }
+    /// The length of the tick in [PER_QUARTER]ths of a quarter
+    pub fn duration(&self) -> u32 {
+        let base = self.value.duration();
+        // every dot adds half of the previous one
+        let dotted = (0..=self.dots as u32).map(|x| base >> x).sum::<u32>();
+        if self.triplet {
+            dotted * 2 / 3
+        } else {
+            dotted
+        }
+    }
+}
+
+/// Parses a line of note lengths above a part, like `  Q   E. S E3 E3 E3 |H`. A letter gives the
+/// note value, followed by up to two dots and a `3` for triplets. Notes can be written without
+/// spaces between them to line up with short ticks.
+/// Returns the column and rhythm of each note.
+pub fn rhythm_line(line: &str) -> Option<Vec<(usize, Rhythm)>> {
+    let mut rhythms = vec![];
+    let mut rem = line;
+    loop {
+        let trimmed = rem.trim_start_matches([' ', '|']);
+        if trimmed.trim_end().is_empty() {
+            break;
+        }
+        let col = char_col_in(line, trimmed);
+        let mut chars = trimmed.chars();
+        let mut rhythm = Rhythm::new(NoteValue::from_letter(chars.next()?)?);
+        let mut after = chars.as_str();
+        while let Some(rest) = after.strip_prefix('.') {
+            rhythm.dots += 1;
+            after = rest;
+        }
+        if let Some(rest) = after.strip_prefix('3') {
+            rhythm.triplet = true;
+            after = rest;
+        }
+        if rhythm.dots > 2 {
+            return None;
+        }
+        rhythms.push((col, rhythm));
+        rem = after;
+    }
+    (!rhythms.is_empty()).then_some(rhythms)
+}
+
+#[test]
+fn test_rhythm_line() {
+    use NoteValue::*;
+    let line = rhythm_line("  Q   E. S |E3 W").unwrap();
+    let cols: Vec<_> = line.iter().map(|x| x.0).collect();
+    assert_eq!(cols, [2, 6, 9, 12, 15]);
+    let durations: Vec<_> = line.iter().map(|x| x.1.duration()).collect();
+    assert_eq!(durations, [96, 72, 24, 32, 384]);
+    assert_eq!(line[1].1, Rhythm { value: Eighth, dots: 1, triplet: false });
+    assert_eq!(rhythm_line("E.SE3").unwrap().len(), 3);
+    assert_eq!(rhythm_line("  Em   A"), None);
+    assert_eq!(rhythm_line("Q Q Quarter"), None);
+    assert_eq!(rhythm_line(" | "), None);
+}
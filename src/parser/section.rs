@@ -0,0 +1,50 @@
+/// The words that make a line like `Verse:` or `Guitar Solo:` a section heading. Headings in
+/// brackets can say anything.
+const SECTIONS: [&str; 16] = [
+    "intro",
+    "verse",
+    "pre-chorus",
+    "prechorus",
+    "chorus",
+    "post-chorus",
+    "refrain",
+    "hook",
+    "bridge",
+    "solo",
+    "interlude",
+    "breakdown",
+    "riff",
+    "instrumental",
+    "outro",
+    "coda",
+];
+
+/// Parses a section heading on its own line, like `[Intro]`, `[Verse 2]` or `Chorus:`. Returns the
+/// name of the section.
+pub fn section_label(line: &str) -> Option<&str> {
+    let line = line.trim();
+    if let Some(name) = line.strip_prefix('[').and_then(|x| x.strip_suffix(']')) {
+        let name = name.trim();
+        // `[1.  ]` is the bracket of an ending
+        let valid = !name.is_empty() && !name.contains(['[', ']', '|']);
+        return (valid && super::ending_line(line).is_none()).then_some(name);
+    }
+    let name = line.strip_suffix(':')?.trim_end();
+    let mut words = name.split_whitespace();
+    let known = words.clone().any(|x| SECTIONS.iter().any(|y| x.eq_ignore_ascii_case(y)));
+    (known && words.nth(3).is_none()).then_some(name)
+}
+
+#[test]
+fn test_section_label() {
+    assert_eq!(section_label("[Intro]"), Some("Intro"));
+    assert_eq!(section_label("  [ Verse 2 ]  "), Some("Verse 2"));
+    assert_eq!(section_label("Chorus:"), Some("Chorus"));
+    assert_eq!(section_label("Guitar Solo:"), Some("Guitar Solo"));
+    assert_eq!(section_label("Bass:"), None);
+    assert_eq!(section_label("Solo over Am:"), Some("Solo over Am"));
+    assert_eq!(section_label("Solo over Am and F:"), None);
+    assert_eq!(section_label("Verse: la la la"), None);
+    assert_eq!(section_label("[]"), None);
+    assert_eq!(section_label("[1.   ]"), None);
+}
@@ -0,0 +1,21 @@
+use super::dialect::Dialect;
+
+/// Options that change how the parser reads a score.
+#[derive(Clone, Debug, Default)]
+pub struct ParserSettings {
+    /// The number of strings of the instrument. When not set, it is detected from the first part.
+    pub string_count: Option<usize>,
+    /// Keep parsing after an error by skipping the measure or part it is in, to find every error
+    /// of the score in one go.
+    pub recover: bool,
+    /// Also accept string lines that are written more loosely, like `|---3---|` or `e:---|`, and
+    /// assume standard tuning when the strings aren't named. Every such guess is reported as a
+    /// diagnostic.
+    pub lenient: bool,
+    /// Record where every element of the tick stream is in the source, in
+    /// [super::ParserResult::spans]. This also makes the locations of errors that the backends find
+    /// in the tick stream exact.
+    pub spans: bool,
+    /// The symbols the score uses for techniques
+    pub dialect: Dialect,
+}
@@ -0,0 +1,57 @@
+use std::ops::Range;
+
+use super::{string_line, ParserRef};
+
+/// The characters of its line that an element of the tick stream was read from. When the tick is
+/// wider than the element, the span covers the whole tick on that string.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ElementSpan {
+    pub start: u32,
+    pub end: u32,
+}
+
+impl ParserRef<'_> {
+    /// The line and the characters of the line that the element at `stream_idx` was read from.
+    /// Only available when parsing with [super::settings::ParserSettings::spans]. See
+    /// [crate::parser::normalize::SourceMap::original_span] for where they are in the input.
+    pub fn source_span(&self, stream_idx: u32) -> Option<(u32, Range<usize>)> {
+        let span = self.spans.get(stream_idx as usize)?;
+        let part = self.offsets.partition_point(|x| x.1 <= stream_idx).checked_sub(1)?;
+        let (part_line, part_start) = self.offsets[part];
+        let string = (stream_idx - part_start) % self.string_count as u32;
+        let line = string_line(self.stray_lines, part_line, string);
+        Some((line, span.start as usize..span.end as usize))
+    }
+    /// The index in the tick stream of the element that was read from the character `col` of the
+    /// line, if there is one. Only available when parsing with
+    /// [super::settings::ParserSettings::spans].
+    pub fn element_at(&self, line: u32, col: u32) -> Option<u32> {
+        if self.spans.is_empty() {
+            return None;
+        }
+        let n = self.string_count as u32;
+        let part = self.offsets.partition_point(|x| x.0 <= line).checked_sub(1)?;
+        let (part_line, part_start) = self.offsets[part];
+        let strays = self.stray_lines.iter().filter(|x| (part_line..=line).contains(*x));
+        if strays.clone().any(|x| *x == line) {
+            return None;
+        }
+        let string = line - part_line - strays.count() as u32;
+        if string >= n {
+            return None;
+        }
+        let part_end = self.offsets.get(part + 1).map_or(self.tick_stream.len() as u32, |x| x.1);
+        let ticks = (part_end - part_start) / n;
+        let span_of = |tick: u32| self.spans[(part_start + tick * n + string) as usize];
+        // the first tick that ends after the column
+        let (mut tick, mut end) = (0, ticks);
+        while tick < end {
+            let mid = (tick + end) / 2;
+            match span_of(mid).end <= col {
+                true => tick = mid + 1,
+                false => end = mid,
+            }
+        }
+        (tick < ticks && span_of(tick).start <= col).then_some(part_start + tick * n + string)
+    }
+}
@@ -0,0 +1,230 @@
+use std::{collections::VecDeque, io::BufRead};
+
+use super::{
+    annotation::annotation_line, instrument::instrument_label, is_line_above,
+    normalize::normalize_line, settings::ParserSettings, Parser, ParserResult,
+};
+use crate::{backend::errors::backend_error::BackendError, ParseLines};
+
+/// The lines of the input that are still needed, indexed like in the whole input. Lines that
+/// were already dropped read as empty.
+#[derive(Default)]
+struct LineWindow {
+    first: usize,
+    lines: VecDeque<String>,
+}
+impl LineWindow {
+    fn drop_before(&mut self, line_idx: usize) {
+        while self.first < line_idx && self.lines.pop_front().is_some() {
+            self.first += 1;
+        }
+    }
+}
+impl ParseLines for LineWindow {
+    fn get_line(&self, idx: usize) -> &str {
+        let line = idx.checked_sub(self.first).and_then(|x| self.lines.get(x));
+        line.map_or("", String::as_str)
+    }
+
+    fn line_count(&self) -> usize {
+        self.first + self.lines.len()
+    }
+}
+
+/// The parser of one instrument of a [PartStream]
+struct InstrumentParser {
+    parser: Parser,
+    tuning_resolved: bool,
+}
+
+/// Parses a score while reading it, and yields every part as soon as it is complete. Only the
+/// lines of the part being parsed are kept in memory, so inputs of any size can be processed.
+///
+/// Every part is a [ParserResult] of its own, with its ticks counted from zero and the line
+/// numbers of the whole input. The directives seen so far are in its metadata. Repeats can't
+/// span parts, as every part is played on its own.
+///
+/// Every line is normalised as it is read, see [normalize_line].
+///
+/// Scores of several instruments are parsed like by [Parser::parse_instruments], and every part
+/// has the label of its instrument. The directives read before a label is first seen are known to
+/// its instrument, the ones after it only to the instrument of the parts they are between.
+///
+/// After an error the stream ends, unless [ParserSettings::recover] is set. Then the errors are
+/// yielded as they are found, before the part they are in.
+pub struct PartStream<R> {
+    reader: R,
+    /// The parser of every instrument seen so far, in the order they first appear
+    parsers: Vec<InstrumentParser>,
+    /// The instrument of the parts being read
+    current: usize,
+    window: LineWindow,
+    /// The first line that was not parsed yet
+    cursor: usize,
+    eof: bool,
+    done: bool,
+    ready: VecDeque<Result<ParserResult, BackendError>>,
+}
+impl<R: BufRead> PartStream<R> {
+    pub fn new(reader: R, settings: ParserSettings) -> Self {
+        Self {
+            reader,
+            parsers: vec![InstrumentParser {
+                parser: Parser::with_settings(settings),
+                tuning_resolved: false,
+            }],
+            current: 0,
+            window: LineWindow::default(),
+            cursor: 0,
+            eof: false,
+            done: false,
+            ready: VecDeque::new(),
+        }
+    }
+    fn parser(&mut self) -> &mut Parser {
+        &mut self.parsers[self.current].parser
+    }
+    /// Makes the parts after the label of an instrument go to its parser.
+    fn switch_instrument(&mut self, label: &str) {
+        let key = |x: &str| x.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+        let known = (self.parsers.iter())
+            .position(|x| x.parser.instrument().is_some_and(|x| key(x) == key(label)));
+        self.current = known.unwrap_or_else(|| {
+            let parser = self.parser().for_instrument(label.to_string());
+            self.parsers.push(InstrumentParser { parser, tuning_resolved: false });
+            self.parsers.len() - 1
+        });
+    }
+    /// Reads lines until `line_idx` is in the window. Returns false if the input ends before it.
+    fn fill(&mut self, line_idx: usize) -> Result<bool, BackendError> {
+        while self.window.line_count() <= line_idx {
+            if self.eof {
+                return Ok(false);
+            }
+            let mut line = vec![];
+            if self.reader.read_until(b'\n', &mut line)? == 0 {
+                self.eof = true;
+                return Ok(false);
+            }
+            if line.ends_with(b"\n") {
+                line.pop();
+            }
+            self.window.lines.push_back(normalize_line(&line));
+        }
+        Ok(true)
+    }
+    /// Parses the text up to and including the next run of string lines. Returns false at the end
+    /// of the input.
+    fn parse_next_run(&mut self) -> Result<bool, BackendError> {
+        loop {
+            if !self.fill(self.cursor)? {
+                return Ok(false);
+            }
+            let line = self.window.get_line(self.cursor);
+            if self.parsers[self.current].parser.is_string_line(line) {
+                break;
+            }
+            if let Some(label) = instrument_label(line) {
+                let label = label.to_string();
+                self.switch_instrument(&label);
+            }
+            let line = self.window.get_line(self.cursor);
+            let parser = &mut self.parsers[self.current].parser;
+            if let Err(err) = parser.parse_directive(line, self.cursor) {
+                parser.recover(err)?;
+            }
+            // the lines above a part are needed until the part is parsed
+            if !is_line_above(line) {
+                self.window.drop_before(self.cursor);
+            }
+            self.cursor += 1;
+        }
+        let mut run_len = 1;
+        while self.fill(self.cursor + run_len)?
+            && (self.parsers[self.current].parser)
+                .is_string_line(self.window.get_line(self.cursor + run_len))
+        {
+            run_len += 1;
+        }
+        // the strings after an empty line or a comment that could be inside of the last part
+        let mut after = self.cursor + run_len + 1;
+        while self.fill(after)?
+            && (self.parsers[self.current].parser).is_string_line(self.window.get_line(after))
+        {
+            after += 1;
+        }
+        let cursor = self.cursor;
+        let after_stray =
+            (self.parsers[self.current].parser).stray_line_run(&self.window, cursor, run_len);
+        let lines_read = run_len + after_stray + (after_stray != 0) as usize;
+        // the annotations below the last part, and the line after them for its lyrics
+        let mut after = self.cursor + lines_read;
+        while self.fill(after)? && annotation_line(self.window.get_line(after)).is_some() {
+            after += 1;
+        }
+        let part_count = (self.parsers[self.current].parser).check_run(
+            &self.window,
+            cursor,
+            run_len + after_stray,
+        )?;
+        let string_count = (run_len + after_stray).checked_div(part_count).unwrap_or(0);
+        for part in 0..part_count {
+            let parser = &mut self.parsers[self.current].parser;
+            if after_stray != 0 && part + 1 == part_count {
+                parser.push_stray_line(&self.window, cursor + run_len);
+            }
+            parser.parse_part_or_skip(&self.window, self.cursor + part * string_count)?;
+            self.finish_part()?;
+        }
+        let errors = self.parser().take_errors();
+        self.ready.extend(errors.into_iter().map(Err));
+        self.cursor += lines_read;
+        // the last string line is kept, so that nothing above the next part is looked for in it
+        self.window.drop_before(self.cursor - 1);
+        Ok(true)
+    }
+    /// Queues the part that was just parsed, after the errors that were found in it.
+    fn finish_part(&mut self) -> Result<(), BackendError> {
+        let InstrumentParser { parser, tuning_resolved } = &mut self.parsers[self.current];
+        // the strings of the first part name the tuning
+        if !*tuning_resolved && parser.has_measures() {
+            *tuning_resolved = true;
+            if let Err(err) = parser.resolve_tuning() {
+                parser.recover(err)?;
+            }
+        }
+        self.ready.extend(parser.take_errors().into_iter().map(Err));
+        let part = parser.take_result();
+        if !part.measures.is_empty() {
+            self.ready.push_back(Ok(part));
+        }
+        Ok(())
+    }
+}
+impl<R: BufRead> Iterator for PartStream<R> {
+    type Item = Result<ParserResult, BackendError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(x) = self.ready.pop_front() {
+                return Some(x);
+            }
+            if self.done {
+                return None;
+            }
+            match self.parse_next_run() {
+                Ok(true) => {}
+                Ok(false) => {
+                    self.done = true;
+                    for x in &mut self.parsers {
+                        self.ready.extend(x.parser.take_errors().into_iter().map(Err));
+                    }
+                }
+                Err(err) => {
+                    self.done = true;
+                    self.ready.push_back(Err(err));
+                }
+            }
+        }
+    }
+}
@@ -1,48 +1,242 @@
-use crate::parser::numeric;
+use crate::parser::{
+    dialect::{Dialect, Technique},
+    numeric,
+};
 use std::cmp::max;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum TabElement {
     Fret(u8),
+    /// A natural harmonic, written as `<12>`
+    NaturalHarmonic(u8),
+    /// An artificial harmonic on `fret`, written as `[7]` (touched an octave above) or `7(19)`
+    /// (touched at fret 19)
+    ArtificialHarmonic {
+        fret: u8,
+        touch: Option<u8>,
+    },
+    /// A ghost note, written as `(5)`
+    GhostNote(u8),
     Rest,
     DeadNote,
-    Bend,
+    /// The previous note on the string is bent, written as `b`, `b9`, `b(9)`, `b½` or `b1`. A
+    /// pre-bend, written as `pb9`, is bent before it is played.
+    Bend {
+        amount: Option<BendAmount>,
+        pre: bool,
+    },
     HammerOn,
     Pull,
-    Release,
-    Slide,
+    /// The bend before it is released, back to the note itself or to fret `to`, like in `7b9r7`
+    Release {
+        to: Option<u8>,
+    },
+    /// A slide up (`/`) or down (`\`). Between two notes it slides from one to the other, before or
+    /// after a single note it slides into it (`/7`) or out of it (`7\`).
+    Slide {
+        down: bool,
+    },
     Vibrato,
+    /// The next note on the string is tapped
+    Tap,
+    /// The previous note on the string is trilled, written as `tr`
+    Trill,
+    /// The previous note on the string is tremolo picked, written as `=`, or as `TP` if `tp` is set.
+    /// Dialects choose between the two with [Technique::TremoloTp].
+    Tremolo {
+        tp: bool,
+    },
+    /// The previous note on the string is accented
+    Accent,
+    /// The previous note on the string is played staccato
+    Staccato,
+}
+
+/// How far a bend goes, as written after the `b`
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum BendAmount {
+    /// `b9`: up to the pitch of this fret. On a fret above the first one, `b1` is a full bend
+    /// instead.
+    Fret(u8),
+    /// `b(9)`, the same as [BendAmount::Fret]
+    Enclosed(u8),
+    /// `b½`
+    Half,
 }
 
 #[derive(Debug)]
 pub enum TabElementError {
     FretTooLarge,
 }
+/// Parses an element. The symbols of techniques are looked up in `dialect`.
 #[inline(always)]
-pub fn tab_element3(s: &str) -> Result<(&str, TabElement), (&str, Option<TabElementError>)> {
+pub fn tab_element3<'a>(
+    s: &'a str, dialect: &Dialect,
+) -> Result<(&'a str, TabElement), (&'a str, Option<TabElementError>)> {
     let bytes = s.as_bytes();
     match bytes.first() {
         Some(b'-') => Ok((&s[1..], TabElement::Rest)),
-        Some(b'x') => Ok((&s[1..], TabElement::DeadNote)),
-        Some(48..=58) => {
+        Some(b'0'..=b'9') => {
             let (res, num) = numeric(s).map_err(|s| (s, Some(TabElementError::FretTooLarge)))?;
-            Ok((res, TabElement::Fret(num)))
+            match enclosed_fret(res, b'(', b')') {
+                Some((res, touch)) => {
+                    Ok((res, TabElement::ArtificialHarmonic { fret: num, touch: Some(touch) }))
+                }
+                None => Ok((res, TabElement::Fret(num))),
+            }
         }
-        Some(b'b') => Ok((&s[1..], TabElement::Bend)),
-        Some(b'h') => Ok((&s[1..], TabElement::HammerOn)),
-        Some(b'p') => Ok((&s[1..], TabElement::Pull)),
-        Some(b'r') => Ok((&s[1..], TabElement::Release)),
-        Some(b'/') | Some(b'\\') => Ok((&s[1..], TabElement::Slide)),
-        Some(b'~') => Ok((&s[1..], TabElement::Vibrato)),
-        Some(_) | None => Err((s, None)),
+        Some(b'<') => match enclosed_fret(s, b'<', b'>') {
+            Some((res, fret)) => Ok((res, TabElement::NaturalHarmonic(fret))),
+            None => Err((s, None)),
+        },
+        Some(b'(') => match enclosed_fret(s, b'(', b')') {
+            Some((res, fret)) => Ok((res, TabElement::GhostNote(fret))),
+            None => Err((s, None)),
+        },
+        Some(b'[') => match enclosed_fret(s, b'[', b']') {
+            Some((res, fret)) => Ok((res, TabElement::ArtificialHarmonic { fret, touch: None })),
+            None => Err((s, None)),
+        },
+        Some(_) => {
+            let Some((res, technique)) = dialect.technique(s) else {
+                return Err((s, None));
+            };
+            let elem = match technique {
+                Technique::Bend => return Ok(bend(res, false)),
+                Technique::PreBend => return Ok(bend(res, true)),
+                Technique::Release => match numeric(res) {
+                    Ok((res, to)) => return Ok((res, TabElement::Release { to: Some(to) })),
+                    Err(_) => TabElement::Release { to: None },
+                },
+                Technique::DeadNote => TabElement::DeadNote,
+                Technique::HammerOn => TabElement::HammerOn,
+                Technique::PullOff => TabElement::Pull,
+                Technique::SlideUp => TabElement::Slide { down: false },
+                Technique::SlideDown => TabElement::Slide { down: true },
+                Technique::Vibrato => TabElement::Vibrato,
+                Technique::Tap => TabElement::Tap,
+                Technique::Trill => TabElement::Trill,
+                Technique::Tremolo => TabElement::Tremolo { tp: false },
+                Technique::TremoloTp => TabElement::Tremolo { tp: true },
+                Technique::Accent => TabElement::Accent,
+                Technique::Staccato => TabElement::Staccato,
+            };
+            Ok((res, elem))
+        }
+        None => Err((s, None)),
     }
 }
+/// Parses the amount of a bend, which is everything after the `b`
+fn bend(s: &str, pre: bool) -> (&str, TabElement) {
+    let (res, amount) = if let Some((res, fret)) = enclosed_fret(s, b'(', b')') {
+        (res, Some(BendAmount::Enclosed(fret)))
+    } else if let Ok((res, fret)) = numeric(s) {
+        (res, Some(BendAmount::Fret(fret)))
+    } else if let Some(res) = s.strip_prefix('½') {
+        (res, Some(BendAmount::Half))
+    } else {
+        (s, None)
+    };
+    (res, TabElement::Bend { amount, pre })
+}
+/// Parses a fret number between `open` and `close`, like `<12>`
+fn enclosed_fret(s: &str, open: u8, close: u8) -> Option<(&str, u8)> {
+    if s.as_bytes().first() != Some(&open) {
+        return None;
+    }
+    let (res, num) = numeric(&s[1..]).ok()?;
+    if res.as_bytes().first() != Some(&close) {
+        return None;
+    }
+    Some((&res[1..], num))
+}
+
+fn digits(x: u8) -> u32 {
+    max(x, 1).ilog10() + 1
+}
+
+/// The interval in semitones between the open string and the sounding pitch of a harmonic
+/// touched at `fret`, or `None` if there is no usable harmonic there.
+pub fn harmonic_interval(fret: u8) -> Option<u8> {
+    Some(match fret {
+        12 => 12,
+        7 | 19 => 19,
+        24 => 24,
+        5 => 24,
+        4 | 9 | 16 => 28,
+        3 => 31,
+        _ => return None,
+    })
+}
+
 impl TabElement {
+    /// Whether this element is a note that gets written in the score
+    pub fn is_note(&self) -> bool {
+        matches!(self, TabElement::DeadNote) || self.sounding_offset().is_some()
+    }
+    /// The sounding pitch of this element above the open string, for anything that produces a
+    /// pitched note. Harmonics on frets without a usable node sound an octave above the fret.
+    pub fn sounding_offset(&self) -> Option<u8> {
+        use TabElement::*;
+        let octave_above = |fret: u8| fret.saturating_add(12);
+        match *self {
+            Fret(x) | GhostNote(x) => Some(x),
+            NaturalHarmonic(x) => Some(harmonic_interval(x).unwrap_or_else(|| octave_above(x))),
+            ArtificialHarmonic { fret, touch: None } => Some(octave_above(fret)),
+            ArtificialHarmonic { fret, touch: Some(touch) } => {
+                let interval = touch.checked_sub(fret).and_then(harmonic_interval);
+                Some(fret.saturating_add(interval.unwrap_or(12)))
+            }
+            _ => None,
+        }
+    }
+
+    /// Where a bend or a release on a note at `fret` ends, in semitones above the note, given how
+    /// far the note is already bent. A bare `b` bends a semitone. `None` for other elements, and
+    /// for bends to a fret that isn't above the note.
+    pub fn bend_target(&self, fret: u8, current: u8) -> Option<u8> {
+        use BendAmount::*;
+        match *self {
+            TabElement::Bend { amount: Some(Fret(x) | Enclosed(x)), .. } if x > fret => {
+                Some(x - fret)
+            }
+            TabElement::Bend { amount: Some(Fret(1)), .. } => Some(current.saturating_add(2)),
+            TabElement::Bend { amount: Some(Fret(_) | Enclosed(_)), .. } => None,
+            TabElement::Bend { amount: Some(Half) | None, .. } => Some(current.saturating_add(1)),
+            TabElement::Release { to } => Some(to.map_or(0, |x| x.saturating_sub(fret))),
+            _ => None,
+        }
+    }
+
+    /// The number of characters this element is written with in the standard dialect
     pub fn repr_len(&self) -> u32 {
         use TabElement::*;
         match self {
-            Fret(x) => max(x, &1).ilog10() + 1,
-            Bend | HammerOn | DeadNote | Pull | Slide | Rest | Release | Vibrato => 1,
+            Bend { amount, pre } => {
+                let amount = match amount {
+                    None => 0,
+                    Some(BendAmount::Fret(x)) => digits(*x),
+                    Some(BendAmount::Enclosed(x)) => digits(*x) + 2,
+                    Some(BendAmount::Half) => 1,
+                };
+                1 + *pre as u32 + amount
+            }
+            Release { to } => 1 + to.map_or(0, digits),
+            Fret(x) => digits(*x),
+            NaturalHarmonic(x) | ArtificialHarmonic { fret: x, touch: None } => digits(*x) + 2,
+            ArtificialHarmonic { fret, touch: Some(touch) } => digits(*fret) + digits(*touch) + 2,
+            GhostNote(x) => digits(*x) + 2,
+            Trill | Tremolo { tp: true } => 2,
+            HammerOn
+            | DeadNote
+            | Pull
+            | Slide { .. }
+            | Accent
+            | Staccato
+            | Rest
+            | Vibrato
+            | Tap
+            | Tremolo { tp: false } => 1,
         }
     }
 }
@@ -0,0 +1,51 @@
+/// What is written after the closing barline of a string line, like `e|--3--|  (x2)`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Trailing {
+    /// A repeat count like `x3` or `(3x)`, which repeats the part
+    RepeatCount(u8),
+    /// Any other text, like `<- let ring`, without the arrow that points at the line
+    Text(String),
+}
+
+/// The text after the closing barline of a string line of a part
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrailingAnnotation {
+    /// The line the text is on
+    pub line: u32,
+    /// The index of the last measure of the part, which the text is written next to
+    pub measure: u32,
+    pub trailing: Trailing,
+}
+
+/// Splits what comes after the closing barline off the end of a line. A repeat count can come
+/// right after the barline, like `|x4`, while other text has to be separated from it by a space,
+/// so that a line without a closing barline isn't taken for one with text after it.
+pub fn strip_trailing(line: &str) -> (&str, Option<Trailing>) {
+    let trimmed = line.trim_end();
+    let Some(bar_idx) = trimmed.rfind('|') else { return (line, None) };
+    let tail = &trimmed[bar_idx + 1..];
+    let text = tail.trim();
+    let inner = text.strip_prefix('(').and_then(|x| x.strip_suffix(')')).unwrap_or(text).trim();
+    let count = inner.strip_prefix(['x', 'X']).or_else(|| inner.strip_suffix(['x', 'X']));
+    if let Some(count) = count.and_then(|x| x.trim().parse().ok()) {
+        return (&trimmed[..=bar_idx], Some(Trailing::RepeatCount(count)));
+    }
+    if text.is_empty() || !tail.starts_with(char::is_whitespace) {
+        return (line, None);
+    }
+    let text = text.trim_start_matches(['<', '-', '=', '←']).trim_start();
+    let trailing = (!text.is_empty()).then(|| Trailing::Text(text.to_string()));
+    (&trimmed[..=bar_idx], trailing)
+}
+
+#[test]
+fn test_strip_trailing() {
+    assert_eq!(strip_trailing("e|--3--|"), ("e|--3--|", None));
+    assert_eq!(strip_trailing("e|--3--|x4"), ("e|--3--|", Some(Trailing::RepeatCount(4))));
+    assert_eq!(strip_trailing("e|--3--|  (3x)"), ("e|--3--|", Some(Trailing::RepeatCount(3))));
+    let let_ring = Some(Trailing::Text("let ring".into()));
+    assert_eq!(strip_trailing("E|--0--|  <- let ring "), ("E|--0--|", let_ring));
+    assert_eq!(strip_trailing("E|--0--|  <-"), ("E|--0--|", None));
+    // without a closing barline, the rest of the line is part of the last measure
+    assert_eq!(strip_trailing("e|--3--|--5"), ("e|--3--|--5", None));
+}
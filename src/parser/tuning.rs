@@ -0,0 +1,111 @@
+use super::numeric;
+
+/// Strings without an octave are resolved upwards from the lowest one, which is placed between
+/// B1 and A#2. This gives the usual octaves for standard, drop and open tunings alike.
+const LOWEST_STRING_FLOOR: u8 = 35;
+
+/// A note name like `E`, `c#`, `Bb` or `D2`, as used in string names and tuning directives.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoteName {
+    /// 0 is C, 1 is C#, ..., 11 is B
+    pub pitch_class: u8,
+    pub octave: Option<u8>,
+}
+
+impl NoteName {
+    /// The MIDI note number of this note, if it has an octave.
+    pub fn midi(&self) -> Option<u8> {
+        let octave = self.octave?;
+        octave.checked_add(1)?.checked_mul(12)?.checked_add(self.pitch_class)
+    }
+}
+
+/// Parses a note name. The letter is case insensitive, because tabs conventionally write the high
+/// e string in lowercase.
+pub fn note_name(s: &str) -> Result<(&str, NoteName), &str> {
+    let pitch_class: u8 = match s.as_bytes().first() {
+        Some(b'C' | b'c') => 0,
+        Some(b'D' | b'd') => 2,
+        Some(b'E' | b'e') => 4,
+        Some(b'F' | b'f') => 5,
+        Some(b'G' | b'g') => 7,
+        Some(b'A' | b'a') => 9,
+        Some(b'B' | b'b') => 11,
+        _ => return Err(s),
+    };
+    let (rem, pitch_class) = match s.as_bytes().get(1) {
+        Some(b'#') => (&s[2..], (pitch_class + 1) % 12),
+        Some(b'b') => (&s[2..], (pitch_class + 11) % 12),
+        _ => (&s[1..], pitch_class),
+    };
+    match numeric(rem) {
+        Ok((rem, octave)) => Ok((rem, NoteName { pitch_class, octave: Some(octave) })),
+        Err(rem) => Ok((rem, NoteName { pitch_class, octave: None })),
+    }
+}
+
+/// Parses the value of a `tuning:` directive, like `D2 A2 D3 G3 B3 E4` or `DADGAD`.
+/// The notes are listed from the lowest string to the highest one.
+pub fn tuning_directive(s: &str) -> Option<Vec<NoteName>> {
+    let mut rem = s.trim_start();
+    let mut names = vec![];
+    while !rem.is_empty() {
+        let (r, name) = note_name(rem).ok()?;
+        names.push(name);
+        rem = r.trim_start();
+    }
+    (!names.is_empty()).then_some(names)
+}
+
+/// The usual tuning of an instrument with this many strings, from the highest string down, for
+/// parts that don't name their strings: guitar for 6 and 7 strings, bass for 4 and 5.
+pub fn standard_tuning(string_count: usize) -> Option<Vec<NoteName>> {
+    let notes: &[(u8, u8)] = match string_count {
+        4 => &[(7, 2), (2, 2), (9, 1), (4, 1)],
+        5 => &[(7, 2), (2, 2), (9, 1), (4, 1), (11, 0)],
+        6 => &[(4, 4), (11, 3), (7, 3), (2, 3), (9, 2), (4, 2)],
+        7 => &[(4, 4), (11, 3), (7, 3), (2, 3), (9, 2), (4, 2), (11, 1)],
+        _ => return None,
+    };
+    let name = |&(pitch_class, octave)| NoteName { pitch_class, octave: Some(octave) };
+    Some(notes.iter().map(name).collect())
+}
+
+/// Turns note names ordered from the highest string to the lowest one (like the lines of a tab)
+/// into the MIDI note numbers of the open strings.
+///
+/// Names with an octave are taken as-is. Names without one are resolved to the lowest matching note
+/// above the string below them.
+pub fn resolve_tuning(names: &[NoteName]) -> Option<Vec<u8>> {
+    let mut tuning = vec![0; names.len()];
+    let mut below: Option<u8> = None;
+    for (s, name) in names.iter().enumerate().rev() {
+        let pc = name.pitch_class;
+        let pitch = match (name.midi(), below) {
+            (Some(pitch), _) => pitch,
+            (None, None) => LOWEST_STRING_FLOOR + (pc + 12 - LOWEST_STRING_FLOOR % 12) % 12,
+            (None, Some(b)) => b.checked_add(1 + (pc + 12 - (b + 1) % 12) % 12)?,
+        };
+        tuning[s] = pitch;
+        below = Some(pitch);
+    }
+    Some(tuning)
+}
+
+#[test]
+fn test_resolve_tuning() {
+    let resolve = |s: &str| {
+        let mut names = tuning_directive(s).unwrap();
+        names.reverse();
+        resolve_tuning(&names).unwrap()
+    };
+    assert_eq!(resolve("E A D G B e"), [64, 59, 55, 50, 45, 40]);
+    assert_eq!(resolve("D A D G B E"), [64, 59, 55, 50, 45, 38]);
+    assert_eq!(resolve("DADGAD"), [62, 57, 55, 50, 45, 38]);
+    assert_eq!(resolve("B E A D G B E"), [64, 59, 55, 50, 45, 40, 35]);
+    assert_eq!(resolve("E1 A1 D2 G2"), [43, 38, 33, 28]);
+    assert_eq!(resolve("C# F# B E G# C#"), [61, 56, 52, 47, 42, 37]);
+    assert_eq!(resolve("Eb Ab Db Gb Bb Eb"), [63, 58, 54, 49, 44, 39]);
+    assert_eq!(resolve_tuning(&standard_tuning(6).unwrap()).unwrap(), resolve("E A D G B e"));
+    assert_eq!(resolve_tuning(&standard_tuning(4).unwrap()).unwrap(), [43, 38, 33, 28]);
+}